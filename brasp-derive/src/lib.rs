@@ -0,0 +1,448 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, GenericArgument, Lit, LitChar, LitStr, Meta, PathArguments, Type};
+
+#[proc_macro_derive(Brasp, attributes(brasp))]
+pub fn derive_brasp(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(Choice, attributes(brasp))]
+pub fn derive_choice(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_choice(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct CommandAttrs {
+    env_prefix: Option<LitStr>,
+    usage: Option<LitStr>,
+    description: Option<LitStr>,
+    help: bool,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    name: Option<LitStr>,
+    short: Option<LitChar>,
+    default: Option<Expr>,
+    description: Option<LitStr>,
+    range: Option<(Expr, Expr)>,
+    pattern: Option<LitStr>,
+    validate: Option<Expr>,
+    validate_with: Option<Expr>,
+    parse_with: Option<Expr>,
+    count: bool,
+    choice: bool,
+    config_file: bool,
+    required: bool,
+    conflicts_with: Vec<LitStr>,
+    requires: Vec<LitStr>,
+    required_unless: Vec<LitStr>,
+    env: Vec<LitStr>,
+    no_env: bool,
+    env_separator: Option<LitChar>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Plain,
+    Option,
+    Vec,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(ident, "#[derive(Brasp)] requires a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "#[derive(Brasp)] can only be used on structs")),
+    };
+
+    let command = command_attrs(&input.attrs)?;
+    let mut settings = TokenStream2::new();
+    if let Some(prefix) = &command.env_prefix {
+        settings.extend(quote! { .env_prefix(#prefix) });
+    }
+    if let Some(usage) = &command.usage {
+        settings.extend(quote! { .usage(#usage) });
+    }
+    if let Some(description) = command.description.or_else(|| doc_comment(&input.attrs)) {
+        settings.extend(quote! { .description(#description) });
+    }
+    if command.help {
+        settings.extend(quote! { .help() });
+    }
+
+    let mut registrations = Vec::new();
+    let mut extractions = Vec::new();
+    for field in fields {
+        let Some(field_ident) = &field.ident else {
+            continue;
+        };
+        let attrs = field_attrs(&field.attrs)?;
+        let name = match &attrs.name {
+            Some(name) => name.value(),
+            None => field_ident.to_string().trim_start_matches("r#").replace('_', "-"),
+        };
+        let (shape, inner) = shape(&field.ty);
+        let kind = match (shape, type_name(inner).as_deref(), attrs.count) {
+            (Shape::Plain | Shape::Option, _, false) if attrs.choice => "opt",
+            (Shape::Vec, _, false) if attrs.choice => "opt_list",
+            (Shape::Plain, Some("i64"), true) => "flag_list",
+            (_, _, true) => return Err(syn::Error::new_spanned(&field.ty, "#[brasp(count)] requires an i64 field")),
+            (Shape::Plain | Shape::Option, Some("String"), _) => "opt",
+            (Shape::Plain | Shape::Option, Some("i64"), _) => "num",
+            (Shape::Plain | Shape::Option, Some("f64"), _) => "float",
+            (Shape::Plain | Shape::Option, Some("u64"), _) => "unsigned",
+            (Shape::Plain | Shape::Option, Some("bool"), _) => "flag",
+            (Shape::Vec, Some("String"), _) => "opt_list",
+            (Shape::Vec, Some("i64"), _) => "num_list",
+            (Shape::Vec, Some("f64"), _) => "float_list",
+            (Shape::Vec, Some("u64"), _) => "unsigned_list",
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "unsupported field type, expected String, i64, f64, u64 or bool, optionally in Option<_>, or Vec<_> of String, i64, f64 or u64",
+                ))
+            }
+        };
+
+        let method = format_ident!("{}", kind);
+        let mut registration = quote! { .#method(#name) };
+        if let Some(short) = &attrs.short {
+            registration.extend(quote! { .short(#short) });
+        }
+        if let Some(description) = attrs.description.clone().or_else(|| doc_comment(&field.attrs)) {
+            registration.extend(quote! { .describe(#description) });
+        }
+        if attrs.choice {
+            registration.extend(quote! { .choices(<#inner as ::brasp::Choice>::CHOICES) });
+        }
+        if let Some(default) = &attrs.default {
+            registration.extend(quote! { .default(#default) });
+        }
+        if let Some((min, max)) = &attrs.range {
+            let range = match kind {
+                "float" | "float_list" => format_ident!("float_range"),
+                "unsigned" | "unsigned_list" => format_ident!("unsigned_range"),
+                _ => format_ident!("range"),
+            };
+            registration.extend(quote! { .#range(#min, #max) });
+        }
+        if let Some(pattern) = &attrs.pattern {
+            registration.extend(quote! { .pattern(#pattern) });
+        }
+        if let Some(validator) = &attrs.validate {
+            registration.extend(quote! { .validate(#validator) });
+        }
+        if let Some(validator) = &attrs.validate_with {
+            registration.extend(quote! { .validate_with(#validator) });
+        }
+        if let Some(parser) = &attrs.parse_with {
+            registration.extend(quote! { .parse_with(#parser) });
+        }
+        if attrs.config_file {
+            registration.extend(quote! { .config_file() });
+        }
+        if attrs.required {
+            registration.extend(quote! { .required() });
+        }
+        for other in &attrs.conflicts_with {
+            registration.extend(quote! { .conflicts_with(#other) });
+        }
+        for other in &attrs.requires {
+            registration.extend(quote! { .requires(#other) });
+        }
+        for other in &attrs.required_unless {
+            registration.extend(quote! { .required_unless(#other) });
+        }
+        for var in &attrs.env {
+            registration.extend(quote! { .env(#var) });
+        }
+        if attrs.no_env {
+            registration.extend(quote! { .no_env() });
+        }
+        if let Some(separator) = &attrs.env_separator {
+            registration.extend(quote! { .env_separator(#separator) });
+        }
+        registration.extend(quote! { .done() });
+        registrations.push(registration);
+
+        let value = match shape {
+            Shape::Plain if kind == "flag" => quote! { parsed.get_or(#name, false)? },
+            Shape::Plain if kind == "flag_list" => quote! { parsed.get_or(#name, 0i64)? },
+            Shape::Plain => quote! { parsed.get(#name)? },
+            Shape::Option => quote! {
+                if parsed.values.contains_key(#name) {
+                    ::std::option::Option::Some(parsed.get(#name)?)
+                } else {
+                    ::std::option::Option::None
+                }
+            },
+            Shape::Vec => quote! {
+                if parsed.values.contains_key(#name) {
+                    parsed.get_list(#name)?
+                } else {
+                    ::std::vec::Vec::new()
+                }
+            },
+        };
+        extractions.push(quote! { #field_ident: #value });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::brasp::FromArgs for #ident #ty_generics #where_clause {
+            fn brasp() -> ::std::result::Result<::brasp::Brasp, ::brasp::DefinitionError> {
+                ::brasp::Brasp::builder() #settings #(#registrations)* .build()
+            }
+
+            fn from_parsed(parsed: &::brasp::OptionsResult) -> ::std::result::Result<Self, ::brasp::ParseError> {
+                ::std::result::Result::Ok(#ident {
+                    #(#extractions,)*
+                })
+            }
+        }
+    })
+}
+
+fn expand_choice(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(ident, "#[derive(Choice)] can only be used on enums"));
+    };
+
+    let mut names = Vec::new();
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(variant, "#[derive(Choice)] requires unit variants"));
+        }
+        let mut name = None;
+        for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("brasp")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown brasp attribute"))
+                }
+            })?;
+        }
+        let name = name.unwrap_or_else(|| kebab_case(&variant.ident.to_string()));
+        let variant_ident = &variant.ident;
+        let pattern = name.to_lowercase();
+        arms.push(quote! { #pattern => ::std::option::Option::Some(#ident::#variant_ident) });
+        names.push(name);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::brasp::Choice for #ident #ty_generics #where_clause {
+            const CHOICES: &'static [&'static str] = &[#(#names),*];
+
+            fn from_choice(choice: &str) -> ::std::option::Option<Self> {
+                match choice.to_lowercase().as_str() {
+                    #(#arms,)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        impl #impl_generics ::brasp::FromValue for #ident #ty_generics #where_clause {
+            fn from_value(value: &::brasp::ValidValue) -> ::std::option::Option<Self> {
+                match value {
+                    ::brasp::ValidValue::String(choice) => <Self as ::brasp::Choice>::from_choice(choice),
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+fn kebab_case(ident: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('-');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn command_attrs(attrs: &[Attribute]) -> syn::Result<CommandAttrs> {
+    let mut out = CommandAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("brasp")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("env_prefix") {
+                out.env_prefix = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("usage") {
+                out.usage = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("description") {
+                out.description = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("help") {
+                out.help = true;
+            } else {
+                return Err(meta.error("unknown brasp attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+fn field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut out = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("brasp")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                out.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("short") {
+                out.short = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("default") {
+                out.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("description") {
+                out.description = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("range") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let min = content.parse()?;
+                content.parse::<syn::Token![,]>()?;
+                let max = content.parse()?;
+                out.range = Some((min, max));
+            } else if meta.path.is_ident("pattern") {
+                out.pattern = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("validate") {
+                out.validate = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("validate_with") {
+                out.validate_with = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("parse_with") {
+                out.parse_with = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("count") {
+                out.count = true;
+            } else if meta.path.is_ident("choice") {
+                out.choice = true;
+            } else if meta.path.is_ident("config_file") {
+                out.config_file = true;
+            } else if meta.path.is_ident("required") {
+                out.required = true;
+            } else if meta.path.is_ident("conflicts_with") {
+                out.conflicts_with.push(meta.value()?.parse()?);
+            } else if meta.path.is_ident("requires") {
+                out.requires.push(meta.value()?.parse()?);
+            } else if meta.path.is_ident("required_unless") {
+                out.required_unless.push(meta.value()?.parse()?);
+            } else if meta.path.is_ident("env") {
+                out.env.push(meta.value()?.parse()?);
+            } else if meta.path.is_ident("no_env") {
+                out.no_env = true;
+            } else if meta.path.is_ident("env_separator") {
+                out.env_separator = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown brasp attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+fn doc_comment(attrs: &[Attribute]) -> Option<LitStr> {
+    let mut lines = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        if let Meta::NameValue(meta) = &attr.meta {
+            if let Expr::Lit(expr) = &meta.value {
+                if let Lit::Str(line) = &expr.lit {
+                    lines.push(line.value().trim().to_string());
+                }
+            }
+        }
+    }
+    let text = lines.join(" ").trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    Some(LitStr::new(&text, proc_macro2::Span::call_site()))
+}
+
+fn shape(ty: &Type) -> (Shape, &Type) {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            let shape = match segment.ident.to_string().as_str() {
+                "Option" => Shape::Option,
+                "Vec" => Shape::Vec,
+                _ => return (Shape::Plain, ty),
+            };
+            if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                    return (shape, inner);
+                }
+            }
+        }
+    }
+    (Shape::Plain, ty)
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::{expand, expand_choice};
+
+    fn error(result: syn::Result<proc_macro2::TokenStream>) -> String {
+        result.expect_err("expansion should fail").to_string()
+    }
+
+    #[test]
+    fn count_requires_i64() {
+        let message = error(expand(parse_quote! {
+            struct Args {
+                #[brasp(count)]
+                verbose: u64,
+            }
+        }));
+        assert_eq!(message, "#[brasp(count)] requires an i64 field");
+        let message = error(expand(parse_quote! {
+            struct Args {
+                #[brasp(count)]
+                verbose: Option<i64>,
+            }
+        }));
+        assert_eq!(message, "#[brasp(count)] requires an i64 field");
+    }
+
+    #[test]
+    fn unsupported_shapes() {
+        assert!(error(expand(parse_quote! { struct Args { size: u32 } })).starts_with("unsupported field type"));
+        assert!(error(expand(parse_quote! { struct Args { flags: Vec<bool> } })).starts_with("unsupported field type"));
+        assert_eq!(error(expand(parse_quote! { struct Args(String); })), "#[derive(Brasp)] requires a struct with named fields");
+        assert_eq!(error(expand_choice(parse_quote! { enum Level { Custom(String) } })), "#[derive(Choice)] requires unit variants");
+    }
+
+    #[test]
+    fn supported_shapes() {
+        assert!(expand(parse_quote! {
+            struct Args {
+                name: String,
+                size: Option<u64>,
+                ratios: Vec<f64>,
+                #[brasp(count)]
+                verbose: i64,
+            }
+        })
+        .is_ok());
+    }
+}
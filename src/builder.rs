@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::{
+    Brasp, ConfigOptionBase, ConfigType, DefinitionError, OptionGroup, ParseError, UnknownPolicy, Validate, ValidValue, Validator,
+    ValueParser,
+};
+
+pub struct BraspBuilder {
+    brasp: Brasp,
+    errors: Vec<ParseError>,
+}
+
+pub struct OptionBuilder {
+    builder: BraspBuilder,
+    name: String,
+    option: ConfigOptionBase,
+}
+
+impl Brasp {
+    pub fn builder() -> BraspBuilder {
+        BraspBuilder {
+            brasp: Brasp::default(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl BraspBuilder {
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.brasp.options.env_prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn usage(mut self, usage: &str) -> Self {
+        self.brasp.options.usage = Some(usage.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.brasp.options.description = Some(description.to_string());
+        self
+    }
+
+    pub fn help(mut self) -> Self {
+        if !self.brasp.options.help {
+            self.errors.extend(self.brasp.help_clashes());
+        }
+        self.brasp.options.help = true;
+        self
+    }
+
+    pub fn allow_positionals(mut self, allow: bool) -> Self {
+        self.brasp.options.allow_positionals = allow;
+        self
+    }
+
+    pub fn stop_at_positional(mut self, stop: bool) -> Self {
+        self.brasp.options.stop_at_positional = stop;
+        self
+    }
+
+    pub fn unknown(mut self, policy: UnknownPolicy) -> Self {
+        self.brasp.options.unknown = policy;
+        self
+    }
+
+    pub fn subcommand(mut self, name: &str, command: Brasp) -> Self {
+        if let Err(e) = self.brasp.subcommand(name, command) {
+            self.errors.extend(e.errors);
+        }
+        self
+    }
+
+    pub fn group(mut self, name: &str, group: OptionGroup) -> Self {
+        if let Err(e) = self.brasp.group(name, group) {
+            self.errors.extend(e.errors);
+        }
+        self
+    }
+
+    pub fn opt(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::String, false)
+    }
+
+    pub fn opt_list(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::String, true)
+    }
+
+    pub fn num(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Number, false)
+    }
+
+    pub fn num_list(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Number, true)
+    }
+
+    pub fn float(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Float, false)
+    }
+
+    pub fn float_list(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Float, true)
+    }
+
+    pub fn unsigned(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Unsigned, false)
+    }
+
+    pub fn unsigned_list(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Unsigned, true)
+    }
+
+    pub fn custom(self, name: &str, type_name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Custom(type_name.to_string()), false)
+    }
+
+    pub fn custom_list(self, name: &str, type_name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Custom(type_name.to_string()), true)
+    }
+
+    pub fn flag(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Boolean, false)
+    }
+
+    pub fn flag_list(self, name: &str) -> OptionBuilder {
+        self.option(name, ConfigType::Boolean, true)
+    }
+
+    pub fn build(mut self) -> Result<Brasp, DefinitionError> {
+        self.errors.extend(self.brasp.check_references());
+        if !self.errors.is_empty() {
+            return Err(DefinitionError { errors: self.errors });
+        }
+        Ok(self.brasp)
+    }
+
+    fn option(self, name: &str, config_type: ConfigType, multiple: bool) -> OptionBuilder {
+        OptionBuilder {
+            builder: self,
+            name: name.to_string(),
+            option: ConfigOptionBase::new(config_type, multiple, None, None),
+        }
+    }
+
+    fn add(&mut self, name: String, option: ConfigOptionBase) {
+        let errors = self.brasp.check_option(&name, &option, &HashMap::new());
+        if errors.is_empty() {
+            self.brasp.insert_option(name, option);
+        } else {
+            self.errors.extend(errors);
+        }
+    }
+}
+
+impl OptionBuilder {
+    pub fn short(mut self, short: char) -> Self {
+        self.option.short = Some(short.to_string());
+        self
+    }
+
+    pub fn describe(mut self, description: &str) -> Self {
+        self.option.description = Some(description.to_string());
+        self
+    }
+
+    pub fn default(mut self, value: impl Into<ValidValue>) -> Self {
+        self.option.default = Some(self.option.coerce_default(value.into()));
+        self
+    }
+
+    pub fn range(mut self, min: i64, max: i64) -> Self {
+        if self.option.config_type != ConfigType::Number {
+            self.invalid(format!("{}..{}", min, max));
+        }
+        self.option.validate = Some(Validator::NumberRange(min, max));
+        self
+    }
+
+    pub fn float_range(mut self, min: f64, max: f64) -> Self {
+        if self.option.config_type != ConfigType::Float {
+            self.invalid(format!("{}..{}", min, max));
+        }
+        self.option.validate = Some(Validator::FloatRange(min, max));
+        self
+    }
+
+    pub fn unsigned_range(mut self, min: u64, max: u64) -> Self {
+        if self.option.config_type != ConfigType::Unsigned {
+            self.invalid(format!("{}..{}", min, max));
+        }
+        self.option.validate = Some(Validator::UnsignedRange(min, max));
+        self
+    }
+
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        if self.option.config_type != ConfigType::String {
+            self.invalid(pattern.to_string());
+        }
+        self.option.validate = Some(Validator::Regex(pattern.into()));
+        self
+    }
+
+    pub fn choices(mut self, choices: &[&str]) -> Self {
+        if self.option.config_type != ConfigType::String {
+            self.invalid(choices.join("|"));
+        }
+        self.option.validate = Some(Validator::Choice(choices.iter().map(|choice| choice.to_string()).collect()));
+        self
+    }
+
+    pub fn validate(mut self, validator: Validator) -> Self {
+        self.option.validate = Some(validator);
+        self
+    }
+
+    pub fn validate_with(mut self, validator: impl Validate + 'static) -> Self {
+        self.option.validate = Some(Validator::Custom(Box::new(validator)));
+        self
+    }
+
+    pub fn parse_with(mut self, parser: impl ValueParser + 'static) -> Self {
+        self.option.parser = Some(Box::new(parser));
+        self
+    }
+
+    pub fn config_file(mut self) -> Self {
+        self.option.config_file = true;
+        self
+    }
+
+    pub fn env(mut self, var: &str) -> Self {
+        self.option.env.push(var.to_string());
+        self
+    }
+
+    pub fn no_env(mut self) -> Self {
+        self.option.no_env = true;
+        self
+    }
+
+    pub fn env_separator(mut self, separator: char) -> Self {
+        self.option.env_separator = Some(separator);
+        self
+    }
+
+    pub fn required(mut self) -> Self {
+        self.option.required = true;
+        self
+    }
+
+    pub fn conflicts_with(mut self, name: &str) -> Self {
+        self.option.conflicts_with.push(name.to_string());
+        self
+    }
+
+    pub fn requires(mut self, name: &str) -> Self {
+        self.option.requires.push(name.to_string());
+        self
+    }
+
+    pub fn required_unless(mut self, name: &str) -> Self {
+        self.option.required_unless.push(name.to_string());
+        self
+    }
+
+    pub fn opt(self, name: &str) -> OptionBuilder {
+        self.done().opt(name)
+    }
+
+    pub fn opt_list(self, name: &str) -> OptionBuilder {
+        self.done().opt_list(name)
+    }
+
+    pub fn num(self, name: &str) -> OptionBuilder {
+        self.done().num(name)
+    }
+
+    pub fn num_list(self, name: &str) -> OptionBuilder {
+        self.done().num_list(name)
+    }
+
+    pub fn float(self, name: &str) -> OptionBuilder {
+        self.done().float(name)
+    }
+
+    pub fn float_list(self, name: &str) -> OptionBuilder {
+        self.done().float_list(name)
+    }
+
+    pub fn unsigned(self, name: &str) -> OptionBuilder {
+        self.done().unsigned(name)
+    }
+
+    pub fn unsigned_list(self, name: &str) -> OptionBuilder {
+        self.done().unsigned_list(name)
+    }
+
+    pub fn custom(self, name: &str, type_name: &str) -> OptionBuilder {
+        self.done().custom(name, type_name)
+    }
+
+    pub fn custom_list(self, name: &str, type_name: &str) -> OptionBuilder {
+        self.done().custom_list(name, type_name)
+    }
+
+    pub fn flag(self, name: &str) -> OptionBuilder {
+        self.done().flag(name)
+    }
+
+    pub fn flag_list(self, name: &str) -> OptionBuilder {
+        self.done().flag_list(name)
+    }
+
+    pub fn build(self) -> Result<Brasp, DefinitionError> {
+        self.done().build()
+    }
+
+    pub fn done(self) -> BraspBuilder {
+        let mut builder = self.builder;
+        builder.add(self.name, self.option);
+        builder
+    }
+
+    fn invalid(&mut self, token: String) {
+        self.builder.errors.push(ParseError::InvalidDefinition {
+            option: self.name.clone(),
+            token,
+            index: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Brasp, OptionGroup, ParseError};
+
+    fn tokens(result: Result<Brasp, crate::DefinitionError>) -> Vec<String> {
+        let errors = result.err().expect("definition should fail").errors;
+        errors
+            .into_iter()
+            .map(|error| match error {
+                ParseError::InvalidDefinition { option, token, .. } => format!("invalid {} {}", option, token),
+                ParseError::DuplicateShort { option, token, .. } => format!("short {} {}", option, token),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    // user-013: bad definitions are collected and reported together.
+    #[test]
+    fn definition_errors() {
+        let result = Brasp::builder()
+            .opt("name")
+            .short('n')
+            .opt("name")
+            .opt("number")
+            .short('n')
+            .opt("word")
+            .range(1, 2)
+            .num("level")
+            .pattern("[a-z]")
+            .num("size")
+            .default("big")
+            .opt("bad name")
+            .build();
+        assert_eq!(
+            tokens(result),
+            vec!["invalid name name", "short number n", "invalid word 1..2", "invalid level [a-z]", "invalid size big", "invalid bad name bad name"]
+        );
+    }
+
+    #[test]
+    fn bad_patterns() {
+        let errors = Brasp::builder().opt("word").pattern("(a").build().err().unwrap().errors;
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ParseError::InvalidDefinition { option, .. } if option == "word"));
+    }
+
+    #[test]
+    fn unknown_references() {
+        let result = Brasp::builder()
+            .flag("a")
+            .conflicts_with("missing")
+            .done()
+            .group("g", OptionGroup { options: vec!["a".to_string(), "gone".to_string()], exclusive: true, required: false })
+            .build();
+        assert_eq!(tokens(result).len(), 2);
+        assert!(Brasp::builder().flag("a").build().is_ok());
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+use crate::ValidValue;
+
+/// Parsers and validators are `Send + Sync` so that `Brasp` stays shareable
+/// across threads.
+pub trait ValueParser: Send + Sync {
+    fn parse(&self, token: &str) -> Result<ValidValue, String>;
+}
+
+pub trait Validate: Send + Sync {
+    fn validate(&self, value: &ValidValue) -> Result<(), String>;
+}
+
+impl<F: Fn(&str) -> Result<ValidValue, String> + Send + Sync> ValueParser for F {
+    fn parse(&self, token: &str) -> Result<ValidValue, String> {
+        self(token)
+    }
+}
+
+impl<F: Fn(&ValidValue) -> Result<(), String> + Send + Sync> Validate for F {
+    fn validate(&self, value: &ValidValue) -> Result<(), String> {
+        self(value)
+    }
+}
+
+impl fmt::Debug for dyn ValueParser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ValueParser")
+    }
+}
+
+impl fmt::Debug for dyn Validate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Validate")
+    }
+}
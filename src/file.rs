@@ -0,0 +1,610 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Brasp, ConfigOptionBase, ConfigType, OptionsResult, ParseError, ValidValue, ValueSource};
+
+#[cfg(feature = "ini")]
+mod ini;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "toml")]
+mod toml;
+
+#[cfg_attr(not(any(feature = "json", feature = "toml")), allow(dead_code))]
+pub(crate) enum FileValue {
+    String(String),
+    Number(String),
+    Boolean(bool),
+    List(Vec<FileValue>),
+}
+
+pub(crate) struct Entry {
+    pub section: Vec<String>,
+    pub key: String,
+    pub value: FileValue,
+    pub line: usize,
+}
+
+pub(crate) struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+pub(crate) struct Loaded {
+    pub section: Vec<String>,
+    pub key: String,
+    pub value: ValidValue,
+    pub source: ValueSource,
+}
+
+const INCLUDE_KEY: &str = "@include";
+
+impl Brasp {
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), ParseError> {
+        for loaded in self.read_config(path.as_ref())? {
+            let Some(option) = self.command_mut(&loaded.section).and_then(|command| command.config_set.get_mut(&loaded.key)) else {
+                continue;
+            };
+            if matches!(option.default_source, ValueSource::Env { .. }) {
+                continue;
+            }
+            option.default = Some(loaded.value);
+            option.default_source = loaded.source;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_config_files(&self, parsed: &mut OptionsResult) -> Result<(), ParseError> {
+        let mut names: Vec<&String> = self.config_set.iter().filter(|(_, option)| option.config_file).map(|(name, _)| name).collect();
+        names.sort();
+        for name in names {
+            let option = &self.config_set[name];
+            let (value, index, required) = match parsed.values.get(name) {
+                Some(value) => {
+                    let index = match parsed.sources.get(name) {
+                        Some(ValueSource::Cli { argv_index }) => Some(*argv_index),
+                        _ => None,
+                    };
+                    (value.clone(), index, true)
+                }
+                None => match &option.default {
+                    Some(value) => (value.clone(), None, matches!(option.default_source, ValueSource::Env { .. })),
+                    None => continue,
+                },
+            };
+            let paths = match value {
+                ValidValue::List(vals) => vals,
+                value => vec![value],
+            };
+            for path in paths {
+                let ValidValue::String(path) = path else {
+                    continue;
+                };
+                let path = Path::new(&path);
+                if !required && !path.exists() {
+                    continue;
+                }
+                let loaded = self.read_config(path).map_err(|mut e| {
+                    if let ParseError::InvalidFile { index: at, .. } = &mut e {
+                        *at = index;
+                    }
+                    e
+                })?;
+                for loaded in loaded {
+                    self.apply_loaded(parsed, loaded);
+                }
+            }
+        }
+        // A subcommand's config files hold its own options, with sections for
+        // its own subcommands.
+        if let Some((name, sub)) = &mut parsed.subcommand {
+            if let Some(command) = self.subcommands.get(name) {
+                command.apply_config_files(sub)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_loaded(&self, parsed: &mut OptionsResult, loaded: Loaded) {
+        let mut brasp = self;
+        let mut result = parsed;
+        for name in &loaded.section {
+            let (Some((chosen, sub)), Some(command)) = (&mut result.subcommand, brasp.subcommands.get(name)) else {
+                return;
+            };
+            if chosen != name {
+                return;
+            }
+            brasp = command;
+            result = sub;
+        }
+        let Some(option) = brasp.config_set.get(&loaded.key) else {
+            return;
+        };
+        let from_cli = matches!(result.sources.get(&loaded.key), Some(ValueSource::Cli { .. }));
+        if from_cli || matches!(option.default_source, ValueSource::Env { .. }) {
+            return;
+        }
+        result.values.insert(loaded.key.clone(), loaded.value);
+        result.sources.insert(loaded.key, loaded.source);
+    }
+
+    pub(crate) fn read_config(&self, path: &Path) -> Result<Vec<Loaded>, ParseError> {
+        let mut loaded = Vec::new();
+        self.read_into(path, &mut Vec::new(), &mut loaded)?;
+        Ok(loaded)
+    }
+
+    fn read_into(&self, path: &Path, stack: &mut Vec<PathBuf>, out: &mut Vec<Loaded>) -> Result<(), ParseError> {
+        if stack.iter().any(|seen| seen == path) {
+            return Err(file_error(path, 0, "", "", "Include cycle".to_string()));
+        }
+        let text = fs::read_to_string(path).map_err(|e| file_error(path, 0, "", "", e.to_string()))?;
+        let entries = parse_file(path, &text).map_err(|e| file_error(path, e.line, "", "", e.message))?;
+
+        stack.push(path.to_path_buf());
+        let mut own: Vec<Loaded> = Vec::new();
+        for entry in entries {
+            if entry.section.is_empty() && entry.key == INCLUDE_KEY {
+                let includes = match &entry.value {
+                    FileValue::List(items) => items.iter().collect(),
+                    value => vec![value],
+                };
+                for include in includes {
+                    let FileValue::String(include) = include else {
+                        return Err(file_error(path, entry.line, INCLUDE_KEY, &include.to_string(), "Expected a file path".to_string()));
+                    };
+                    let include = path.parent().unwrap_or(Path::new("")).join(include);
+                    self.read_into(&include, stack, out)?;
+                }
+                continue;
+            }
+
+            let (key, option) = self.resolve(path, &entry)?;
+            let Some(value) = coerce(option, &entry.value) else {
+                let token = entry.value.to_string();
+                let reason = format!("Invalid value {:?} for option {}", token, key);
+                return Err(file_error(path, entry.line, &key, &token, reason));
+            };
+            let accumulates = option.multiple && option.config_type != ConfigType::Boolean && !matches!(value, ValidValue::List(_));
+            let previous = own.iter_mut().find(|loaded| loaded.section == entry.section && loaded.key == key);
+            match (previous, accumulates) {
+                (Some(Loaded { value: ValidValue::List(vals), .. }), true) => vals.push(value),
+                _ => own.push(Loaded {
+                    section: entry.section,
+                    key,
+                    value: if accumulates { ValidValue::List(vec![value]) } else { value },
+                    source: ValueSource::File {
+                        path: path.to_path_buf(),
+                        line: entry.line,
+                    },
+                }),
+            }
+        }
+        stack.pop();
+        out.extend(own);
+        Ok(())
+    }
+
+    fn resolve(&self, path: &Path, entry: &Entry) -> Result<(String, &ConfigOptionBase), ParseError> {
+        let mut brasp = self;
+        for name in &entry.section {
+            brasp = brasp
+                .subcommands
+                .get(name)
+                .ok_or_else(|| file_error(path, entry.line, name, "", format!("Unknown section {}", name)))?;
+        }
+        let key = match brasp.config_set.contains_key(&entry.key) {
+            true => entry.key.clone(),
+            false => entry.key.replace('_', "-"),
+        };
+        match brasp.config_set.get(&key) {
+            Some(option) => Ok((key, option)),
+            None => Err(file_error(path, entry.line, &entry.key, "", format!("Unknown config option: {}", entry.key))),
+        }
+    }
+
+    fn command_mut(&mut self, section: &[String]) -> Option<&mut Brasp> {
+        let mut brasp = self;
+        for name in section {
+            brasp = brasp.subcommands.get_mut(name)?;
+        }
+        Some(brasp)
+    }
+}
+
+impl fmt::Display for FileValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileValue::String(val) | FileValue::Number(val) => write!(f, "{}", val),
+            FileValue::Boolean(val) => write!(f, "{}", val),
+            FileValue::List(vals) => {
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", val)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[allow(unused_variables)]
+fn parse_file(path: &Path, text: &str) -> Result<Vec<Entry>, SyntaxError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "toml")]
+        Some("toml") => toml::parse(text),
+        #[cfg(feature = "json")]
+        Some("json") => json::parse(text),
+        #[cfg(feature = "ini")]
+        Some("ini" | "cfg" | "conf") => ini::parse(text),
+        _ => Err(SyntaxError {
+            line: 0,
+            message: "Unsupported config file format".to_string(),
+        }),
+    }
+}
+
+fn coerce(option: &ConfigOptionBase, value: &FileValue) -> Option<ValidValue> {
+    match value {
+        FileValue::List(items) if option.multiple && option.config_type != ConfigType::Boolean => {
+            items.iter().map(|item| coerce_scalar(option, item)).collect::<Option<Vec<_>>>().map(ValidValue::List)
+        }
+        _ => coerce_scalar(option, value),
+    }
+}
+
+fn coerce_scalar(option: &ConfigOptionBase, value: &FileValue) -> Option<ValidValue> {
+    let text = match value {
+        FileValue::String(text) | FileValue::Number(text) => text,
+        FileValue::Boolean(b) if option.config_type == ConfigType::Boolean && !option.multiple => return Some(ValidValue::Boolean(*b)),
+        FileValue::Boolean(_) | FileValue::List(_) => return None,
+    };
+    option.value_from_str(text).ok()
+}
+
+fn file_error(path: &Path, line: usize, option: &str, token: &str, reason: String) -> ParseError {
+    ParseError::InvalidFile {
+        option: option.to_string(),
+        token: token.to_string(),
+        index: None,
+        path: path.to_path_buf(),
+        line,
+        reason,
+    }
+}
+
+#[cfg(any(feature = "json", feature = "toml"))]
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+#[cfg(any(feature = "json", feature = "toml"))]
+impl Cursor {
+    fn new(text: &str) -> Self {
+        Cursor {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SyntaxError> {
+        if !self.eat(c) {
+            return Err(self.error(format!("Expected '{}'", c)));
+        }
+        Ok(())
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.peek().filter(|c| f(*c)) {
+            out.push(c);
+            self.bump();
+        }
+        out
+    }
+
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, SyntaxError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) if c != '\n' => c,
+                _ => return Err(self.error("Unterminated string")),
+            };
+            self.bump();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let c = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        Some('u') => self.unicode_escape(4)?,
+                        Some('U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    out.push(c);
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, len: usize) -> Result<char, SyntaxError> {
+        let mut hex = String::new();
+        for _ in 0..len {
+            hex.extend(self.bump());
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[cfg(any(feature = "toml", feature = "json", feature = "ini"))]
+    use crate::{ValidValue, ValueSource};
+    use crate::{Brasp, ParseError};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("brasp-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, file: &str, text: &str) -> PathBuf {
+        let path = dir.join(file);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn sample() -> Brasp {
+        let remote = Brasp::builder().opt("url").build().unwrap();
+        Brasp::builder()
+            .num("level")
+            .default(1)
+            .flag("verbose")
+            .opt("log-level")
+            .opt_list("include")
+            .unsigned("size")
+            .done()
+            .subcommand("remote", remote)
+            .build()
+            .unwrap()
+    }
+
+    #[cfg(any(feature = "toml", feature = "json", feature = "ini"))]
+    fn loaded(file: &str, text: &str) -> Brasp {
+        let dir = scratch_dir(file);
+        let mut brasp = sample();
+        brasp.load_file(write(&dir, file, text)).unwrap();
+        brasp
+    }
+
+    #[cfg(any(feature = "toml", feature = "json", feature = "ini"))]
+    fn default_of(brasp: &Brasp, name: &str) -> String {
+        brasp.config_set[name].default.as_ref().map(ValidValue::to_string).unwrap_or_default()
+    }
+
+    fn line_of(result: Result<(), ParseError>) -> usize {
+        match result {
+            Err(ParseError::InvalidFile { line, .. }) => line,
+            other => panic!("expected InvalidFile, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_values() {
+        let brasp = loaded(
+            "values.toml",
+            "# comment\nlevel = +0x10\nverbose = true # trailing\nlog_level = \"debug\"\ninclude = [\"a\", 'b']\nsize = 1_000\n\n[remote]\nurl = \"https://example.com\"\n",
+        );
+        assert_eq!(default_of(&brasp, "level"), "16");
+        assert_eq!(default_of(&brasp, "verbose"), "true");
+        assert_eq!(default_of(&brasp, "log-level"), "debug");
+        assert_eq!(default_of(&brasp, "include"), "a,b");
+        assert_eq!(default_of(&brasp, "size"), "1000");
+        assert_eq!(default_of(&brasp.subcommands["remote"], "url"), "https://example.com");
+        assert_eq!(brasp.config_set["level"].default_source, ValueSource::File { path: scratch_dir("values.toml").join("values.toml"), line: 2 });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_values() {
+        let brasp = loaded(
+            "values.json",
+            "{\n  \"level\": 7,\n  \"verbose\": false,\n  \"include\": [\"a\", \"b\"],\n  \"remote\": { \"url\": \"x\\u0041\" }\n}\n",
+        );
+        assert_eq!(default_of(&brasp, "level"), "7");
+        assert_eq!(default_of(&brasp, "verbose"), "false");
+        assert_eq!(default_of(&brasp, "include"), "a,b");
+        assert_eq!(default_of(&brasp.subcommands["remote"], "url"), "xA");
+        assert_eq!(brasp.config_set["include"].default_source, ValueSource::File { path: scratch_dir("values.json").join("values.json"), line: 4 });
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn ini_values() {
+        let brasp = loaded(
+            "values.ini",
+            "; comment\n# comment\nlevel = 3 ; note\nverbose = yes # note\nlog-level = \"a ; b\"\ninclude = a\ninclude = b\nsize: 0b11\n\n[remote] ; section\nurl = http://host/#frag\n",
+        );
+        assert_eq!(default_of(&brasp, "level"), "3");
+        assert_eq!(default_of(&brasp, "verbose"), "true");
+        assert_eq!(default_of(&brasp, "log-level"), "a ; b");
+        assert_eq!(default_of(&brasp, "include"), "a,b");
+        assert_eq!(default_of(&brasp, "size"), "3");
+        assert_eq!(default_of(&brasp.subcommands["remote"], "url"), "http://host/#frag");
+    }
+
+    #[cfg(any(feature = "toml", feature = "json", feature = "ini"))]
+    fn assert_error_lines(cases: &[(&str, &str, usize)]) {
+        let dir = scratch_dir("errors");
+        for (file, text, line) in cases {
+            let result = sample().load_file(write(&dir, file, text));
+            assert_eq!(line_of(result), *line, "{}", file);
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_error_lines() {
+        assert_error_lines(&[("bad.toml", "level = 1\n\nlevel = \"x\n", 3), ("unknown.toml", "level = 1\nnope = 2\n", 2)]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_error_lines() {
+        assert_error_lines(&[("bad.json", "{\n  \"level\": 1,\n  \"verbose\": nope\n}\n", 3), ("type.json", "{\n\n  \"level\": \"x\"\n}\n", 3)]);
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn ini_error_lines() {
+        assert_error_lines(&[("bad.ini", "level = 1\n[remote\n", 2), ("value.ini", "\n\n\nlevel = x\n", 4), ("section.ini", "[nope]\nurl = 1\n", 2)]);
+    }
+
+    #[test]
+    fn unreadable_files() {
+        let dir = scratch_dir("unreadable");
+        assert_eq!(line_of(sample().load_file(dir.join("missing.toml"))), 0);
+        assert_eq!(line_of(sample().load_file(write(&dir, "plain.txt", "level = 1"))), 0);
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn includes() {
+        let dir = scratch_dir("includes");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        write(&dir, "sub/base.ini", "level = 2\nlog-level = base\n");
+        let main = write(&dir, "main.ini", "@include = sub/base.ini\nlevel = 5\n");
+        let mut brasp = sample();
+        brasp.load_file(&main).unwrap();
+        assert_eq!(default_of(&brasp, "level"), "5");
+        assert_eq!(default_of(&brasp, "log-level"), "base");
+
+        write(&dir, "a.ini", "@include = b.ini\n");
+        write(&dir, "b.ini", "@include = a.ini\n");
+        match sample().load_file(dir.join("a.ini")) {
+            Err(ParseError::InvalidFile { reason, path, .. }) => {
+                assert_eq!(reason, "Include cycle");
+                assert_eq!(path, dir.join("a.ini"));
+            }
+            other => panic!("expected an include cycle, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn layering() {
+        let dir = scratch_dir("layering");
+        let file = write(&dir, "layers.ini", "level = 2\nlog-level = file\nsize = 10\n");
+        env::set_var("BRASP_LAYERS_LOG_LEVEL", "env");
+        env::set_var("BRASP_LAYERS_SIZE", "20");
+        let mut brasp = Brasp::builder()
+            .env_prefix("BRASP_LAYERS")
+            .num("level")
+            .default(1)
+            .num("depth")
+            .default(1)
+            .opt("log-level")
+            .unsigned("size")
+            .build()
+            .unwrap();
+        // Env defaults win over the file even when they are read first.
+        brasp.set_defaults_from_env().unwrap();
+        brasp.load_file(&file).unwrap();
+        let parsed = brasp.parse(vec!["--size".to_string(), "30".to_string()]).unwrap();
+
+        assert_eq!(parsed.get_i64("depth").unwrap(), 1);
+        assert_eq!(parsed.sources["depth"], ValueSource::Default);
+        assert_eq!(parsed.get_i64("level").unwrap(), 2);
+        assert_eq!(parsed.sources["level"], ValueSource::File { path: file, line: 1 });
+        assert_eq!(parsed.get_str("log-level").unwrap(), "env");
+        assert_eq!(parsed.sources["log-level"], ValueSource::Env { var: "BRASP_LAYERS_LOG_LEVEL".to_string() });
+        assert_eq!(parsed.get_u64("size").unwrap(), 30);
+        assert_eq!(parsed.sources["size"], ValueSource::Cli { argv_index: 0 });
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn config_file_option_in_subcommand() {
+        let dir = scratch_dir("config-subcommand");
+        let file = write(&dir, "run.ini", "level = 5\n");
+        let run = Brasp::builder().opt("config").config_file().num("level").default(1).build().unwrap();
+        let brasp = Brasp::builder().flag("verbose").done().subcommand("run", run).build().unwrap();
+        let parsed = brasp.parse(vec!["run".to_string(), "--config".to_string(), file.display().to_string()]).unwrap();
+        let (_, run) = parsed.subcommand.as_ref().unwrap();
+        assert_eq!(run.get_i64("level").unwrap(), 5);
+        assert_eq!(run.sources["level"], ValueSource::File { path: file, line: 1 });
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn config_file_option() {
+        let dir = scratch_dir("config-option");
+        let first = write(&dir, "first.ini", "level = 2\nlog-level = first\n");
+        let second = write(&dir, "second.ini", "level = 3\n");
+        let brasp = Brasp::builder().opt_list("config").config_file().num("level").default(1).opt("log-level").build().unwrap();
+        let args = |extra: &[&str]| {
+            let mut args = vec!["--config".to_string(), first.display().to_string(), "--config".to_string(), second.display().to_string()];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            args
+        };
+        let parsed = brasp.parse(args(&[])).unwrap();
+        assert_eq!(parsed.get_i64("level").unwrap(), 3);
+        assert_eq!(parsed.get_str("log-level").unwrap(), "first");
+        let parsed = brasp.parse(args(&["--level", "9"])).unwrap();
+        assert_eq!(parsed.get_i64("level").unwrap(), 9);
+
+        match brasp.parse(vec!["--config".to_string(), dir.join("missing.ini").display().to_string()]) {
+            Err(ParseError::InvalidFile { index, .. }) => assert_eq!(index, Some(0)),
+            other => panic!("expected InvalidFile, got {:?}", other),
+        }
+    }
+}
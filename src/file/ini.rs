@@ -0,0 +1,65 @@
+use super::{Entry, FileValue, SyntaxError};
+
+pub(crate) fn parse(text: &str) -> Result<Vec<Entry>, SyntaxError> {
+    let mut entries = Vec::new();
+    let mut section = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let trimmed = strip_comment(raw).trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[') {
+            let name = name.strip_suffix(']').ok_or_else(|| SyntaxError {
+                line,
+                message: "Expected ']' after section name".to_string(),
+            })?;
+            section = name.split('.').map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect();
+            continue;
+        }
+        let (key, value) = match trimmed.split_once(['=', ':']) {
+            Some((key, value)) if !key.trim().is_empty() => (key.trim(), value.trim()),
+            _ => {
+                return Err(SyntaxError {
+                    line,
+                    message: "Expected key = value".to_string(),
+                })
+            }
+        };
+        entries.push(Entry {
+            section: section.clone(),
+            key: key.to_string(),
+            value: FileValue::String(unquote(value).to_string()),
+            line,
+        });
+    }
+    Ok(entries)
+}
+
+/// Cuts the line at a `;` or `#` that starts it or follows whitespace,
+/// unless it is inside a quoted value.
+fn strip_comment(raw: &str) -> &str {
+    let mut quote = None;
+    let mut prev = None;
+    for (i, c) in raw.char_indices() {
+        let after_space = prev.is_none_or(|p: char| p.is_whitespace());
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if matches!(c, ';' | '#') && after_space => return &raw[..i],
+            None if matches!(c, '"' | '\'') && prev.is_none_or(|p| p.is_whitespace() || matches!(p, '=' | ':')) => quote = Some(c),
+            None => {}
+        }
+        prev = Some(c);
+    }
+    raw
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|value| value.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
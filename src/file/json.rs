@@ -0,0 +1,84 @@
+use super::{Cursor, Entry, FileValue, SyntaxError};
+
+pub(crate) fn parse(text: &str) -> Result<Vec<Entry>, SyntaxError> {
+    let mut cursor = Cursor::new(text);
+    let mut entries = Vec::new();
+    skip_whitespace(&mut cursor);
+    object(&mut cursor, &[], &mut entries)?;
+    skip_whitespace(&mut cursor);
+    if cursor.peek().is_some() {
+        return Err(cursor.error("Unexpected content after the top-level object"));
+    }
+    Ok(entries)
+}
+
+fn object(cursor: &mut Cursor, section: &[String], entries: &mut Vec<Entry>) -> Result<(), SyntaxError> {
+    cursor.expect('{')?;
+    skip_whitespace(cursor);
+    if cursor.eat('}') {
+        return Ok(());
+    }
+    loop {
+        skip_whitespace(cursor);
+        let line = cursor.line;
+        let key = cursor.basic_string()?;
+        skip_whitespace(cursor);
+        cursor.expect(':')?;
+        skip_whitespace(cursor);
+        if cursor.peek() == Some('{') {
+            let mut nested = section.to_vec();
+            nested.push(key);
+            object(cursor, &nested, entries)?;
+        } else {
+            let value = value(cursor)?;
+            entries.push(Entry {
+                section: section.to_vec(),
+                key,
+                value,
+                line,
+            });
+        }
+        skip_whitespace(cursor);
+        if !cursor.eat(',') {
+            return cursor.expect('}');
+        }
+    }
+}
+
+fn value(cursor: &mut Cursor) -> Result<FileValue, SyntaxError> {
+    match cursor.peek() {
+        Some('"') => Ok(FileValue::String(cursor.basic_string()?)),
+        Some('[') => {
+            cursor.bump();
+            let mut items = Vec::new();
+            skip_whitespace(cursor);
+            if cursor.eat(']') {
+                return Ok(FileValue::List(items));
+            }
+            loop {
+                skip_whitespace(cursor);
+                items.push(value(cursor)?);
+                skip_whitespace(cursor);
+                if !cursor.eat(',') {
+                    cursor.expect(']')?;
+                    return Ok(FileValue::List(items));
+                }
+            }
+        }
+        Some('{') => Err(cursor.error("Objects are only supported as sections")),
+        _ => {
+            let token = cursor.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'));
+            match token.as_str() {
+                "" => Err(cursor.error("Expected value")),
+                "true" => Ok(FileValue::Boolean(true)),
+                "false" => Ok(FileValue::Boolean(false)),
+                "null" => Err(cursor.error("null is not supported")),
+                _ => Ok(FileValue::Number(token)),
+            }
+        }
+    }
+}
+
+fn skip_whitespace(cursor: &mut Cursor) {
+    cursor.take_while(char::is_whitespace);
+}
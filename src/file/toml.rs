@@ -0,0 +1,122 @@
+use super::{Cursor, Entry, FileValue, SyntaxError};
+
+pub(crate) fn parse(text: &str) -> Result<Vec<Entry>, SyntaxError> {
+    let mut cursor = Cursor::new(text);
+    let mut entries = Vec::new();
+    let mut table = Vec::new();
+    loop {
+        skip_blank(&mut cursor);
+        if cursor.peek().is_none() {
+            return Ok(entries);
+        }
+        if cursor.eat('[') {
+            if cursor.peek() == Some('[') {
+                return Err(cursor.error("Arrays of tables are not supported"));
+            }
+            table = key_path(&mut cursor)?;
+            cursor.expect(']')?;
+            line_end(&mut cursor)?;
+            continue;
+        }
+        let line = cursor.line;
+        let mut path = key_path(&mut cursor)?;
+        cursor.expect('=')?;
+        skip_spaces(&mut cursor);
+        let value = value(&mut cursor)?;
+        line_end(&mut cursor)?;
+        let key = path.pop().unwrap_or_default();
+        let mut section = table.clone();
+        section.extend(path);
+        entries.push(Entry { section, key, value, line });
+    }
+}
+
+fn key_path(cursor: &mut Cursor) -> Result<Vec<String>, SyntaxError> {
+    let mut path = Vec::new();
+    loop {
+        skip_spaces(cursor);
+        let key = match cursor.peek() {
+            Some('"') => cursor.basic_string()?,
+            Some('\'') => literal_string(cursor)?,
+            _ => cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+        };
+        if key.is_empty() {
+            return Err(cursor.error("Expected key"));
+        }
+        path.push(key);
+        skip_spaces(cursor);
+        if !cursor.eat('.') {
+            return Ok(path);
+        }
+    }
+}
+
+fn value(cursor: &mut Cursor) -> Result<FileValue, SyntaxError> {
+    match cursor.peek() {
+        Some('"') => Ok(FileValue::String(cursor.basic_string()?)),
+        Some('\'') => Ok(FileValue::String(literal_string(cursor)?)),
+        Some('[') => {
+            cursor.bump();
+            let mut items = Vec::new();
+            loop {
+                skip_blank(cursor);
+                if cursor.eat(']') {
+                    return Ok(FileValue::List(items));
+                }
+                items.push(value(cursor)?);
+                skip_blank(cursor);
+                if !cursor.eat(',') {
+                    cursor.expect(']')?;
+                    return Ok(FileValue::List(items));
+                }
+            }
+        }
+        Some('{') => Err(cursor.error("Inline tables are not supported")),
+        _ => {
+            let token = cursor.take_while(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '#'));
+            match token.as_str() {
+                "" => Err(cursor.error("Expected value")),
+                "true" => Ok(FileValue::Boolean(true)),
+                "false" => Ok(FileValue::Boolean(false)),
+                _ => Ok(FileValue::Number(token.trim_start_matches('+').replace('_', ""))),
+            }
+        }
+    }
+}
+
+fn literal_string(cursor: &mut Cursor) -> Result<String, SyntaxError> {
+    cursor.expect('\'')?;
+    let value = cursor.take_while(|c| c != '\'' && c != '\n');
+    cursor.expect('\'')?;
+    Ok(value)
+}
+
+fn skip_blank(cursor: &mut Cursor) {
+    loop {
+        match cursor.peek() {
+            Some(c) if c.is_whitespace() => {
+                cursor.bump();
+            }
+            Some('#') => {
+                cursor.take_while(|c| c != '\n');
+            }
+            _ => return,
+        }
+    }
+}
+
+fn line_end(cursor: &mut Cursor) -> Result<(), SyntaxError> {
+    skip_spaces(cursor);
+    if cursor.peek() == Some('#') {
+        cursor.take_while(|c| c != '\n');
+    }
+    cursor.eat('\r');
+    match cursor.peek() {
+        None | Some('\n') => Ok(()),
+        Some(_) => Err(cursor.error("Expected end of line")),
+    }
+}
+
+fn skip_spaces(cursor: &mut Cursor) {
+    cursor.take_while(|c| c == ' ' || c == '\t');
+}
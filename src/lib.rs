@@ -1,19 +1,48 @@
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
+use std::path::PathBuf;
 
-pub type ConfigType = String;
+mod builder;
+mod custom;
+mod file;
+mod regex;
+mod usage;
+
+pub use builder::{BraspBuilder, OptionBuilder};
+pub use custom::{Validate, ValueParser};
+pub use regex::Pattern;
+
+#[cfg(feature = "derive")]
+pub use brasp_derive::{Brasp, Choice};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ConfigType {
+    #[default]
+    String,
+    Number,
+    Float,
+    Unsigned,
+    Boolean,
+    Custom(String),
+}
 
 #[derive(Debug, Clone)]
 pub enum ValidValue {
     Number(i64),
+    Float(f64),
+    Unsigned(u64),
     String(String),
     Boolean(bool),
+    List(Vec<ValidValue>),
 }
 
+#[derive(Default)]
 pub struct Brasp {
     pub config_set: HashMap<String, ConfigOptionBase>,
     pub short_options: HashMap<String, String>,
+    pub subcommands: HashMap<String, Brasp>,
+    pub groups: HashMap<String, OptionGroup>,
     pub options: BraspOptions,
 }
 
@@ -22,78 +51,229 @@ pub struct BraspOptions {
     pub allow_positionals: bool,
     pub env_prefix: Option<String>,
     pub usage: Option<String>,
+    pub description: Option<String>,
+    pub help: bool,
+    pub stop_at_positional: bool,
+    pub unknown: UnknownPolicy,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnknownPolicy {
+    Error,
+    #[default]
+    Collect,
+    Positional,
+}
+
+#[derive(Debug, Default)]
 pub struct ConfigOptionBase {
     pub config_type: ConfigType,
     pub short: Option<String>,
     pub default: Option<ValidValue>,
+    pub default_source: ValueSource,
     pub description: Option<String>,
     pub validate: Option<Validator>,
+    pub parser: Option<Box<dyn ValueParser>>,
     pub multiple: bool,
+    pub required: bool,
+    pub conflicts_with: Vec<String>,
+    pub requires: Vec<String>,
+    pub required_unless: Vec<String>,
+    pub config_file: bool,
+    pub env: Vec<String>,
+    pub no_env: bool,
+    pub env_separator: Option<char>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OptionGroup {
+    pub options: Vec<String>,
+    pub exclusive: bool,
+    pub required: bool,
 }
 
 #[derive(Debug)]
 pub enum Validator {
     NumberRange(i64, i64),
-    Regex(String),
+    FloatRange(f64, f64),
+    UnsignedRange(u64, u64),
+    Regex(Pattern),
+    Choice(Vec<String>),
+    Custom(Box<dyn Validate>),
     None,
 }
 
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ValueSource {
+    Cli { argv_index: usize },
+    Env { var: String },
+    File { path: PathBuf, line: usize },
+    #[default]
+    Default,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    InvalidNumber { option: String, token: String, index: Option<usize> },
+    MissingValue { option: String, token: String, index: Option<usize> },
+    UnknownOption { option: String, token: String, index: Option<usize>, suggestion: Option<String> },
+    DuplicateShort { option: String, token: String, index: Option<usize> },
+    ValidationFailed { option: String, token: String, index: Option<usize> },
+    InvalidDefinition { option: String, token: String, index: Option<usize> },
+    UnexpectedPositional { option: String, token: String, index: Option<usize> },
+    MissingOption { option: String, token: String, index: Option<usize> },
+    TypeMismatch { option: String, token: String, index: Option<usize> },
+    ConflictingOptions { option: String, token: String, index: Option<usize> },
+    MissingGroup { option: String, token: String, index: Option<usize> },
+    InvalidValue { option: String, token: String, index: Option<usize>, reason: String },
+    InvalidFile { option: String, token: String, index: Option<usize>, path: PathBuf, line: usize, reason: String },
+    InvalidEnv { option: String, token: String, index: Option<usize>, var: String, reason: String },
+    HelpRequested { option: String, token: String, index: Option<usize>, usage: String },
+}
+
 impl Brasp {
-    pub fn num(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "number".to_string();
-            self.config_set.insert(name, option);
-        }
+    pub fn num(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Number, false)
     }
 
-    pub fn num_list(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "number".to_string();
-            option.multiple = true;
-            self.config_set.insert(name, option);
-        }
+    pub fn num_list(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Number, true)
+    }
+
+    pub fn float(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Float, false)
+    }
+
+    pub fn float_list(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Float, true)
+    }
+
+    pub fn unsigned(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Unsigned, false)
+    }
+
+    pub fn unsigned_list(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Unsigned, true)
+    }
+
+    pub fn opt(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::String, false)
+    }
+
+    pub fn opt_list(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::String, true)
+    }
+
+    pub fn custom(&mut self, type_name: &str, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Custom(type_name.to_string()), false)
     }
 
-    pub fn opt(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "string".to_string();
-            self.config_set.insert(name, option);
+    pub fn custom_list(&mut self, type_name: &str, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Custom(type_name.to_string()), true)
+    }
+
+    pub fn flag(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Boolean, false)
+    }
+
+    pub fn flag_list(&mut self, fields: HashMap<String, ConfigOptionBase>) -> Result<(), DefinitionError> {
+        self.register(fields, ConfigType::Boolean, true)
+    }
+
+    fn register(&mut self, fields: HashMap<String, ConfigOptionBase>, config_type: ConfigType, multiple: bool) -> Result<(), DefinitionError> {
+        let mut fields: Vec<(String, ConfigOptionBase)> = fields.into_iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut errors = Vec::new();
+        let mut shorts = HashMap::new();
+        for (name, option) in &mut fields {
+            option.config_type = config_type.clone();
+            option.multiple |= multiple;
+            option.default = option.default.take().map(|default| option.coerce_default(default));
+            errors.extend(self.check_option(name, option, &shorts));
+            if let Some(short) = &option.short {
+                shorts.entry(short.clone()).or_insert_with(|| name.clone());
+            }
         }
+        if !errors.is_empty() {
+            return Err(DefinitionError { errors });
+        }
+
+        for (name, option) in fields {
+            self.insert_option(name, option);
+        }
+        Ok(())
     }
 
-    pub fn opt_list(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "string".to_string();
-            option.multiple = true;
-            self.config_set.insert(name, option);
+    pub(crate) fn check_option(&self, name: &str, option: &ConfigOptionBase, pending_shorts: &HashMap<String, String>) -> Vec<ParseError> {
+        let mut errors = Vec::new();
+        let invalid = |token: &str| ParseError::InvalidDefinition {
+            option: name.to_string(),
+            token: token.to_string(),
+            index: None,
+        };
+        if !is_valid_name(name) || self.config_set.contains_key(name) {
+            errors.push(invalid(name));
         }
+        if self.options.help {
+            errors.extend(help_clash(name, option));
+        }
+        if let Some(short) = &option.short {
+            if short.is_empty() || short.starts_with('-') || short.contains('=') {
+                errors.push(invalid(short));
+            } else if (self.short_options.contains_key(short) || pending_shorts.contains_key(short)) && !(self.options.help && short == "h") {
+                errors.push(ParseError::DuplicateShort {
+                    option: name.to_string(),
+                    token: short.clone(),
+                    index: None,
+                });
+            }
+        }
+        if let Err(e) = option.check_definition(name) {
+            errors.push(e);
+        }
+        if let Some(default) = option.default.as_ref().filter(|default| !option.accepts(default)) {
+            errors.push(invalid(&default.to_string()));
+        }
+        errors
     }
 
-    pub fn flag(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "boolean".to_string();
-            self.config_set.insert(name, option);
+    /// Reports options in this command and its subcommands that would be
+    /// shadowed by the built-in `-h`/`--help`.
+    pub(crate) fn help_clashes(&self) -> Vec<ParseError> {
+        let mut names: Vec<&String> = self.config_set.keys().collect();
+        names.sort();
+        let mut errors: Vec<ParseError> = names.into_iter().flat_map(|name| help_clash(name, &self.config_set[name])).collect();
+        let mut commands: Vec<&String> = self.subcommands.keys().collect();
+        commands.sort();
+        for command in commands {
+            errors.extend(self.subcommands[command].help_clashes());
         }
+        errors
     }
 
-    pub fn flag_list(&mut self, fields: HashMap<String, ConfigOptionBase>) {
-        for (name, mut option) in fields {
-            option.config_type = "boolean".to_string();
-            option.multiple = true;
-            self.config_set.insert(name, option);
+    pub(crate) fn insert_option(&mut self, name: String, option: ConfigOptionBase) {
+        if let Some(short) = &option.short {
+            self.short_options.insert(short.clone(), name.clone());
         }
+        self.config_set.insert(name, option);
     }
 
-    pub fn validate_name(&mut self, name: &str, option: &ConfigOptionBase) -> Result<(), String> {
-        if !name.chars().all(char::is_alphanumeric) {
-            return Err(format!("Invalid option name: {}, must be alphanumeric.", name));
+    pub fn validate_name(&mut self, name: &str, option: &ConfigOptionBase) -> Result<(), ParseError> {
+        if !is_valid_name(name) {
+            return Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: name.to_string(),
+                index: None,
+            });
         }
         if let Some(short) = &option.short {
             if self.short_options.contains_key(short) {
-                return Err(format!("Short option {} is already in use.", short));
+                return Err(ParseError::DuplicateShort {
+                    option: name.to_string(),
+                    token: short.clone(),
+                    index: None,
+                });
             }
             self.short_options.insert(short.clone(), name.to_string());
         }
@@ -101,65 +281,501 @@ impl Brasp {
     }
 
     pub fn write_env(&self, parsed: &OptionsResult) {
-        if let Some(prefix) = &self.options.env_prefix {
-            for (field, value) in &parsed.values {
-                let env_key = to_env_key(prefix, field);
-                let env_value = to_env_val(value);
-                env::set_var(env_key, env_value);
+        self.write_env_with(parsed, None);
+    }
+
+    fn write_env_with(&self, parsed: &OptionsResult, inherited: Option<&str>) {
+        let prefix = self.options.env_prefix.as_deref().or(inherited);
+        for (field, value) in &parsed.values {
+            let Some(option) = self.config_set.get(field) else {
+                continue;
+            };
+            if let Some(env_key) = option.env_keys(prefix, field).into_iter().next() {
+                env::set_var(env_key, option.value_to_env(value));
+            }
+        }
+        if let Some((name, sub)) = &parsed.subcommand {
+            if let Some(command) = self.subcommands.get(name) {
+                command.write_env_with(sub, prefix);
             }
         }
     }
 }
 
+impl fmt::Display for ConfigType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigType::String => write!(f, "string"),
+            ConfigType::Number => write!(f, "number"),
+            ConfigType::Float => write!(f, "float"),
+            ConfigType::Unsigned => write!(f, "unsigned"),
+            ConfigType::Boolean => write!(f, "boolean"),
+            ConfigType::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<i64> for ValidValue {
+    fn from(value: i64) -> Self {
+        ValidValue::Number(value)
+    }
+}
+
+impl From<u64> for ValidValue {
+    fn from(value: u64) -> Self {
+        ValidValue::Unsigned(value)
+    }
+}
+
+/// Unsuffixed integer literals fall back to `i32` once `i64` and `u64` both
+/// convert, so this keeps `.default(3)` compiling.
+impl From<i32> for ValidValue {
+    fn from(value: i32) -> Self {
+        ValidValue::Number(value.into())
+    }
+}
+
+impl From<f64> for ValidValue {
+    fn from(value: f64) -> Self {
+        ValidValue::Float(value)
+    }
+}
+
+impl From<bool> for ValidValue {
+    fn from(value: bool) -> Self {
+        ValidValue::Boolean(value)
+    }
+}
+
+impl From<&str> for ValidValue {
+    fn from(value: &str) -> Self {
+        ValidValue::String(value.to_string())
+    }
+}
+
+impl From<String> for ValidValue {
+    fn from(value: String) -> Self {
+        ValidValue::String(value)
+    }
+}
+
+impl<T: Into<ValidValue>> From<Vec<T>> for ValidValue {
+    fn from(values: Vec<T>) -> Self {
+        ValidValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl Default for BraspOptions {
+    fn default() -> Self {
+        BraspOptions {
+            allow_positionals: true,
+            env_prefix: None,
+            usage: None,
+            description: None,
+            help: false,
+            stop_at_positional: false,
+            unknown: UnknownPolicy::default(),
+        }
+    }
+}
+
 impl fmt::Display for ValidValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ValidValue::Number(val) => write!(f, "{}", val),
+            ValidValue::Float(val) => write!(f, "{}", val),
+            ValidValue::Unsigned(val) => write!(f, "{}", val),
             ValidValue::String(val) => write!(f, "{}", val),
             ValidValue::Boolean(val) => write!(f, "{}", val),
+            ValidValue::List(vals) => {
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", val)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl ParseError {
+    pub fn option(&self) -> &str {
+        match self {
+            ParseError::InvalidNumber { option, .. }
+            | ParseError::MissingValue { option, .. }
+            | ParseError::UnknownOption { option, .. }
+            | ParseError::DuplicateShort { option, .. }
+            | ParseError::ValidationFailed { option, .. }
+            | ParseError::InvalidDefinition { option, .. }
+            | ParseError::UnexpectedPositional { option, .. }
+            | ParseError::MissingOption { option, .. }
+            | ParseError::TypeMismatch { option, .. }
+            | ParseError::ConflictingOptions { option, .. }
+            | ParseError::MissingGroup { option, .. }
+            | ParseError::InvalidValue { option, .. }
+            | ParseError::InvalidFile { option, .. }
+            | ParseError::InvalidEnv { option, .. }
+            | ParseError::HelpRequested { option, .. } => option,
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        match self {
+            ParseError::InvalidNumber { token, .. }
+            | ParseError::MissingValue { token, .. }
+            | ParseError::UnknownOption { token, .. }
+            | ParseError::DuplicateShort { token, .. }
+            | ParseError::ValidationFailed { token, .. }
+            | ParseError::InvalidDefinition { token, .. }
+            | ParseError::UnexpectedPositional { token, .. }
+            | ParseError::MissingOption { token, .. }
+            | ParseError::TypeMismatch { token, .. }
+            | ParseError::ConflictingOptions { token, .. }
+            | ParseError::MissingGroup { token, .. }
+            | ParseError::InvalidValue { token, .. }
+            | ParseError::InvalidFile { token, .. }
+            | ParseError::InvalidEnv { token, .. }
+            | ParseError::HelpRequested { token, .. } => token,
+        }
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            ParseError::InvalidNumber { index, .. }
+            | ParseError::MissingValue { index, .. }
+            | ParseError::UnknownOption { index, .. }
+            | ParseError::DuplicateShort { index, .. }
+            | ParseError::ValidationFailed { index, .. }
+            | ParseError::InvalidDefinition { index, .. }
+            | ParseError::UnexpectedPositional { index, .. }
+            | ParseError::MissingOption { index, .. }
+            | ParseError::TypeMismatch { index, .. }
+            | ParseError::ConflictingOptions { index, .. }
+            | ParseError::MissingGroup { index, .. }
+            | ParseError::InvalidValue { index, .. }
+            | ParseError::InvalidFile { index, .. }
+            | ParseError::InvalidEnv { index, .. }
+            | ParseError::HelpRequested { index, .. } => *index,
+        }
+    }
+
+    pub fn is_help(&self) -> bool {
+        matches!(self, ParseError::HelpRequested { .. })
+    }
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueSource::Cli { argv_index } => write!(f, "command line argument {}", argv_index),
+            ValueSource::Env { var } => write!(f, "environment variable {}", var),
+            ValueSource::File { path, line } => write!(f, "config file {}:{}", path.display(), line),
+            ValueSource::Default => write!(f, "default value"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidNumber { option, token, .. } => write!(f, "Invalid number {:?} for option {}", token, option)?,
+            ParseError::MissingValue { option, token, .. } => write!(f, "Missing value for option {} ({})", option, token)?,
+            ParseError::UnknownOption { option, .. } => write!(f, "Unknown config option: {}", option)?,
+            ParseError::DuplicateShort { option, token, .. } => write!(f, "Short option {} of {} is already in use.", token, option)?,
+            ParseError::ValidationFailed { option, token, .. } => write!(f, "Invalid value {:?} for option {}", token, option)?,
+            ParseError::InvalidDefinition { option, token, .. } => write!(f, "Invalid definition {:?} for option {}", token, option)?,
+            ParseError::UnexpectedPositional { token, .. } => write!(f, "Unexpected positional argument {:?}", token)?,
+            ParseError::MissingOption { option, token, .. } if !token.is_empty() => {
+                write!(f, "Missing required option {} (required by {})", option, token)?
+            }
+            ParseError::MissingOption { option, .. } => write!(f, "Missing required option {}", option)?,
+            ParseError::TypeMismatch { option, token, .. } => write!(f, "Value {:?} of option {} has an unexpected type", token, option)?,
+            ParseError::ConflictingOptions { option, token, .. } => write!(f, "Option {} cannot be used with {}", option, token)?,
+            ParseError::MissingGroup { option, token, .. } => write!(f, "One of {} is required by group {}", token, option)?,
+            ParseError::InvalidValue { option, token, reason, .. } => write!(f, "Invalid value {:?} for option {}: {}", token, option, reason)?,
+            ParseError::InvalidFile { path, line, reason, .. } if *line > 0 => write!(f, "{}:{}: {}", path.display(), line, reason)?,
+            ParseError::InvalidFile { path, reason, .. } => write!(f, "{}: {}", path.display(), reason)?,
+            ParseError::InvalidEnv { option, token, var, reason, .. } => {
+                write!(f, "Invalid value {:?} for option {} from environment variable {}: {}", token, option, var, reason)?
+            }
+            ParseError::HelpRequested { usage, .. } => return write!(f, "{}", usage),
+        }
+        if let Some(index) = self.index() {
+            write!(f, " at argument {}", index)?;
+        }
+        if let ParseError::UnknownOption { suggestion: Some(suggestion), .. } = self {
+            write!(f, ", did you mean {}?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A batch of errors reported together, either by definition checks
+/// (`DefinitionError`) or by validation of parsed values (`ValidationError`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Errors {
+    pub errors: Vec<ParseError>,
+}
+
+pub type DefinitionError = Errors;
+pub type ValidationError = Errors;
+
+impl Errors {
+    pub fn is_help(&self) -> bool {
+        self.errors.iter().any(ParseError::is_help)
+    }
+}
+
+impl fmt::Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Errors {}
+
+impl From<ParseError> for Errors {
+    fn from(e: ParseError) -> Self {
+        Errors { errors: vec![e] }
+    }
+}
+
 impl ConfigOptionBase {
     pub fn new(config_type: ConfigType, multiple: bool, short: Option<String>, description: Option<String>) -> Self {
         ConfigOptionBase {
             config_type,
             short,
             default: None,
+            default_source: ValueSource::Default,
             description,
             validate: None,
+            parser: None,
             multiple,
+            required: false,
+            conflicts_with: Vec::new(),
+            requires: Vec::new(),
+            required_unless: Vec::new(),
+            config_file: false,
+            env: Vec::new(),
+            no_env: false,
+            env_separator: None,
+        }
+    }
+
+    pub fn check_definition(&self, name: &str) -> Result<(), ParseError> {
+        if self.env_separator == Some('\\') {
+            return Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: "\\".to_string(),
+                index: None,
+            });
+        }
+        if self.no_env && !self.env.is_empty() {
+            return Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: self.env.join(","),
+                index: None,
+            });
+        }
+        if self.config_file && self.config_type != ConfigType::String {
+            return Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: self.config_type.to_string(),
+                index: None,
+            });
+        }
+        match &self.validate {
+            Some(Validator::Regex(pattern)) => pattern.compile().map_err(|_| ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: pattern.to_string(),
+                index: None,
+            }),
+            Some(Validator::Choice(choices)) if choices.is_empty() => Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: String::new(),
+                index: None,
+            }),
+            Some(Validator::NumberRange(min, max)) if min > max => Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: format!("{}..{}", min, max),
+                index: None,
+            }),
+            Some(Validator::FloatRange(min, max)) if min > max || min.is_nan() || max.is_nan() => Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: format!("{}..{}", min, max),
+                index: None,
+            }),
+            Some(Validator::UnsignedRange(min, max)) if min > max => Err(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: format!("{}..{}", min, max),
+                index: None,
+            }),
+            _ => Ok(()),
         }
     }
 
     pub fn validate_value(&self, value: &ValidValue) -> bool {
+        if let ValidValue::List(vals) = value {
+            return self.multiple && vals.iter().all(|val| self.validate_value(val));
+        }
+        if self.multiple && self.config_type == ConfigType::Boolean {
+            return matches!(value, ValidValue::Number(count) if *count >= 0);
+        }
         if let Some(ref validate) = self.validate {
             match validate {
-                Validator::Regex(ref regex) => return matches!(value, ValidValue::String(s) if regex == s),
+                Validator::Regex(ref pattern) => return matches!(value, ValidValue::String(s) if pattern.is_match(s)),
                 Validator::NumberRange(min, max) => return matches!(value, ValidValue::Number(num) if *num >= *min && *num <= *max),
+                Validator::FloatRange(min, max) => return matches!(value, ValidValue::Float(num) if *num >= *min && *num <= *max),
+                Validator::UnsignedRange(min, max) => return matches!(value, ValidValue::Unsigned(num) if *num >= *min && *num <= *max),
+                Validator::Choice(choices) => return matches!(value, ValidValue::String(s) if choose(choices, s).is_some()),
+                Validator::Custom(validator) => return validator.validate(value).is_ok(),
                 Validator::None => return true,
             }
         }
-        match (self.config_type.as_str(), value) {
-            ("string", ValidValue::String(_)) => true,
-            ("number", ValidValue::Number(_)) => true,
-            ("boolean", ValidValue::Boolean(_)) => true,
-            _ => false,
+        self.type_matches(value)
+    }
+
+    pub fn choices(&self) -> Option<&[String]> {
+        match &self.validate {
+            Some(Validator::Choice(choices)) => Some(choices),
+            _ => None,
+        }
+    }
+
+    /// Integer literals become `ValidValue::Number`, so a non-negative one
+    /// given as the default of an unsigned option is stored as `Unsigned`.
+    pub(crate) fn coerce_default(&self, value: ValidValue) -> ValidValue {
+        match value {
+            ValidValue::Number(n) if self.config_type == ConfigType::Unsigned && n >= 0 => ValidValue::Unsigned(n as u64),
+            ValidValue::List(vals) => ValidValue::List(vals.into_iter().map(|val| self.coerce_default(val)).collect()),
+            value => value,
+        }
+    }
+
+    pub(crate) fn canonicalize(&self, value: ValidValue) -> ValidValue {
+        match (self.choices(), value) {
+            (Some(choices), ValidValue::String(s)) => match choose(choices, &s) {
+                Some(choice) => ValidValue::String(choice.clone()),
+                None => ValidValue::String(s),
+            },
+            (_, value) => value,
+        }
+    }
+
+    pub fn env_keys(&self, prefix: Option<&str>, name: &str) -> Vec<String> {
+        if self.no_env {
+            return Vec::new();
+        }
+        if !self.env.is_empty() {
+            return self.env.clone();
+        }
+        prefix.map(|prefix| to_env_key(prefix, name)).into_iter().collect()
+    }
+
+    pub fn separator(&self) -> char {
+        self.env_separator.unwrap_or(',')
+    }
+
+    pub(crate) fn value_from_env(&self, text: &str) -> Result<ValidValue, String> {
+        if !self.multiple || self.config_type == ConfigType::Boolean {
+            return self.value_from_str(text);
+        }
+        split_env_list(text, self.separator())
+            .iter()
+            .map(|item| self.value_from_str(item))
+            .collect::<Result<Vec<_>, _>>()
+            .map(ValidValue::List)
+    }
+
+    pub(crate) fn value_to_env(&self, value: &ValidValue) -> String {
+        match value {
+            ValidValue::List(vals) => join_env_list(vals, self.separator()),
+            value => to_env_val(value),
+        }
+    }
+
+    pub(crate) fn value_from_str(&self, text: &str) -> Result<ValidValue, String> {
+        if let Some(parser) = &self.parser {
+            return parser.parse(text);
+        }
+        if self.multiple && self.config_type == ConfigType::Boolean {
+            return parse_number(text, &ConfigType::Number).ok_or_else(|| "expected a count".to_string());
+        }
+        convert(text, &self.config_type).map(|value| self.canonicalize(value))
+    }
+
+    pub(crate) fn accepts(&self, value: &ValidValue) -> bool {
+        self.type_matches(value) && self.validate_value(value)
+    }
+
+    fn type_matches(&self, value: &ValidValue) -> bool {
+        match value {
+            ValidValue::List(vals) => self.multiple && vals.iter().all(|val| self.type_matches(val)),
+            ValidValue::Number(_) if self.multiple && self.config_type == ConfigType::Boolean => true,
+            _ => matches!(
+                (&self.config_type, value),
+                (ConfigType::String, ValidValue::String(_))
+                    | (ConfigType::Custom(_), _)
+                    | (ConfigType::Number, ValidValue::Number(_))
+                    | (ConfigType::Float, ValidValue::Float(_))
+                    | (ConfigType::Unsigned, ValidValue::Unsigned(_))
+                    | (ConfigType::Boolean, ValidValue::Boolean(_))
+            ),
         }
     }
 }
 
+fn help_clash(name: &str, option: &ConfigOptionBase) -> Vec<ParseError> {
+    let mut errors = Vec::new();
+    if name == "help" {
+        errors.push(ParseError::InvalidDefinition {
+            option: name.to_string(),
+            token: name.to_string(),
+            index: None,
+        });
+    }
+    if option.short.as_deref() == Some("h") {
+        errors.push(ParseError::DuplicateShort {
+            option: name.to_string(),
+            token: "h".to_string(),
+            index: None,
+        });
+    }
+    errors
+}
+
 pub fn to_env_key(prefix: &str, key: &str) -> String {
-    format!("{}_{}", prefix.to_uppercase(), key.to_uppercase())
+    format!("{}_{}", prefix.to_uppercase(), key.to_uppercase().replace('-', "_"))
 }
 
-pub fn from_env_val(env: &str, config_type: &str) -> ValidValue {
+pub fn from_env_val(env: &str, config_type: &ConfigType) -> Result<ValidValue, ParseError> {
+    convert(env, config_type).map_err(|reason| ParseError::InvalidValue {
+        option: String::new(),
+        token: env.to_string(),
+        index: None,
+        reason,
+    })
+}
+
+fn convert(text: &str, config_type: &ConfigType) -> Result<ValidValue, String> {
     match config_type {
-        "string" => ValidValue::String(env.to_string()),
-        "number" => ValidValue::Number(env.parse().unwrap()),
-        "boolean" => ValidValue::Boolean(env == "1"),
-        _ => panic!("Unknown config type"),
+        ConfigType::String | ConfigType::Custom(_) => Ok(ValidValue::String(text.to_string())),
+        ConfigType::Number => parse_number(text, config_type).ok_or_else(|| "expected a number".to_string()),
+        ConfigType::Float => parse_number(text, config_type).ok_or_else(|| "expected a floating-point number".to_string()),
+        ConfigType::Unsigned => parse_number(text, config_type).ok_or_else(|| "expected an unsigned number".to_string()),
+        ConfigType::Boolean => parse_bool(text)
+            .map(ValidValue::Boolean)
+            .ok_or_else(|| "expected true/false, yes/no, on/off or 1/0".to_string()),
     }
 }
 
@@ -167,95 +783,1217 @@ pub fn to_env_val(value: &ValidValue) -> String {
     match value {
         ValidValue::String(v) => v.clone(),
         ValidValue::Number(v) => v.to_string(),
+        ValidValue::Float(v) => v.to_string(),
+        ValidValue::Unsigned(v) => v.to_string(),
         ValidValue::Boolean(v) => if *v { "1".to_string() } else { "0".to_string() },
+        ValidValue::List(v) => join_env_list(v, ','),
     }
 }
 
-pub fn validate_options(config: &ConfigOptionBase, value: &ValidValue) -> Result<(), String> {
+pub fn join_env_list(values: &[ValidValue], separator: char) -> String {
+    let mut out = String::new();
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.push(separator);
+        }
+        for c in to_env_val(value).chars() {
+            if c == separator || c == '\\' {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn split_env_list(text: &str, separator: char) -> Vec<String> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => item.push(chars.next().unwrap_or('\\')),
+            c if c == separator => items.push(std::mem::take(&mut item)),
+            c => item.push(c),
+        }
+    }
+    items.push(item);
+    items
+}
+
+pub fn validate_options(name: &str, config: &ConfigOptionBase, value: &ValidValue) -> Result<(), ParseError> {
+    let values = match value {
+        ValidValue::List(vals) => vals.iter().collect(),
+        _ => vec![value],
+    };
+    for val in values {
+        let rejected = match &config.validate {
+            Some(Validator::Custom(validator)) => validator.validate(val).err(),
+            Some(Validator::Choice(choices)) if !config.validate_value(val) => {
+                Some(format!("expected one of {}", choices.join(", ")))
+            }
+            _ => None,
+        };
+        if let Some(reason) = rejected {
+            return Err(ParseError::InvalidValue {
+                option: name.to_string(),
+                token: val.to_string(),
+                index: None,
+                reason,
+            });
+        }
+    }
     if !config.validate_value(value) {
-        return Err(format!("Invalid value {:?} for option", value));
+        return Err(ParseError::ValidationFailed {
+            option: name.to_string(),
+            token: value.to_string(),
+            index: None,
+        });
     }
     Ok(())
 }
 
-#[derive(Debug)]
+pub trait FromValue: Sized {
+    fn from_value(value: &ValidValue) -> Option<Self>;
+}
+
+impl FromValue for ValidValue {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        match value {
+            ValidValue::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        match value {
+            ValidValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for u64 {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        match value {
+            ValidValue::Unsigned(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        match value {
+            ValidValue::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &ValidValue) -> Option<Self> {
+        match value {
+            ValidValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+pub trait Choice: Sized {
+    const CHOICES: &'static [&'static str];
+
+    fn from_choice(choice: &str) -> Option<Self>;
+}
+
+pub trait FromArgs: Sized {
+    fn brasp() -> Result<Brasp, DefinitionError>;
+
+    fn from_parsed(parsed: &OptionsResult) -> Result<Self, ParseError>;
+
+    fn from_args() -> Result<Self, ValidationError> {
+        Self::from_arg_list(env::args().skip(1).collect())
+    }
+
+    fn from_arg_list(args: Vec<String>) -> Result<Self, ValidationError> {
+        let mut brasp = Self::brasp()?;
+        brasp.set_defaults_from_env()?;
+        let parsed = brasp.parse(args)?;
+        brasp.validate_parsed(&parsed)?;
+        Ok(Self::from_parsed(&parsed)?)
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct OptionsResult {
     pub values: HashMap<String, ValidValue>,
+    pub sources: HashMap<String, ValueSource>,
     pub positionals: Vec<String>,
+    pub unknown: Vec<String>,
+    pub subcommand: Option<(String, Box<OptionsResult>)>,
+}
+
+impl OptionsResult {
+    pub fn command_path(&self) -> Vec<&str> {
+        let mut path = Vec::new();
+        let mut current = self;
+        while let Some((name, sub)) = &current.subcommand {
+            path.push(name.as_str());
+            current = sub;
+        }
+        path
+    }
+
+    pub fn command(&self) -> &OptionsResult {
+        match &self.subcommand {
+            Some((_, sub)) => sub.command(),
+            None => self,
+        }
+    }
+
+    pub fn get<T: FromValue>(&self, name: &str) -> Result<T, ParseError> {
+        let value = self.require(name)?;
+        T::from_value(value).ok_or_else(|| self.mismatch(name, value))
+    }
+
+    pub fn get_or<T: FromValue>(&self, name: &str, default: T) -> Result<T, ParseError> {
+        match self.values.get(name) {
+            Some(value) => T::from_value(value).ok_or_else(|| self.mismatch(name, value)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn get_str(&self, name: &str) -> Result<&str, ParseError> {
+        match self.require(name)? {
+            ValidValue::String(s) => Ok(s),
+            value => Err(self.mismatch(name, value)),
+        }
+    }
+
+    pub fn get_i64(&self, name: &str) -> Result<i64, ParseError> {
+        self.get(name)
+    }
+
+    pub fn get_f64(&self, name: &str) -> Result<f64, ParseError> {
+        self.get(name)
+    }
+
+    pub fn get_u64(&self, name: &str) -> Result<u64, ParseError> {
+        self.get(name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Result<bool, ParseError> {
+        self.get(name)
+    }
+
+    pub fn get_list<T: FromValue>(&self, name: &str) -> Result<Vec<T>, ParseError> {
+        match self.require(name)? {
+            ValidValue::List(vals) => vals
+                .iter()
+                .map(|val| T::from_value(val).ok_or_else(|| self.mismatch(name, val)))
+                .collect(),
+            value => Ok(vec![T::from_value(value).ok_or_else(|| self.mismatch(name, value))?]),
+        }
+    }
+
+    fn require(&self, name: &str) -> Result<&ValidValue, ParseError> {
+        self.values.get(name).ok_or_else(|| ParseError::MissingOption {
+            option: name.to_string(),
+            token: String::new(),
+            index: None,
+        })
+    }
+
+    fn mismatch(&self, name: &str, value: &ValidValue) -> ParseError {
+        let index = match self.sources.get(name) {
+            Some(ValueSource::Cli { argv_index }) => Some(*argv_index),
+            _ => None,
+        };
+        ParseError::TypeMismatch {
+            option: name.to_string(),
+            token: value.to_string(),
+            index,
+        }
+    }
+
+    fn record(&mut self, name: &str, config: &ConfigOptionBase, value: ValidValue, argv_index: usize) {
+        insert_value(&mut self.values, name, config, value);
+        self.sources.insert(name.to_string(), ValueSource::Cli { argv_index });
+    }
+
+    pub fn source(&self, name: &str) -> Option<&ValueSource> {
+        self.sources.get(name)
+    }
+
+    pub fn explain(&self, name: &str) -> Option<String> {
+        let value = self.values.get(name)?;
+        match self.sources.get(name) {
+            Some(source) => Some(format!("{} = {} (from {})", name, value, source)),
+            None => Some(format!("{} = {} (source unknown)", name, value)),
+        }
+    }
+
+    pub fn explain_all(&self) -> String {
+        let mut names: Vec<&String> = self.values.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| self.explain(name))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+struct Scope<'a> {
+    name: &'a str,
+    brasp: &'a Brasp,
+    result: OptionsResult,
 }
 
 impl Brasp {
-    pub fn parse_raw(&self, args: Vec<String>) -> OptionsResult {
-        let mut values = HashMap::new();
-        let mut positionals = Vec::new();
+    pub fn parse_raw(&self, args: Vec<String>) -> Result<OptionsResult, ParseError> {
+        let mut scopes = vec![Scope {
+            name: "",
+            brasp: self,
+            result: OptionsResult::default(),
+        }];
+        let mut rest = None;
         let mut i = 0;
-        
+
         while i < args.len() {
             let arg = &args[i];
-            if arg.starts_with("--") {
-                let key = &arg[2..];
-                if let Some(config) = self.config_set.get(key) {
-                    if config.config_type == "boolean" {
-                        values.insert(key.to_string(), ValidValue::Boolean(true));
-                    } else if i + 1 < args.len() {
-                        let val = &args[i + 1];
-                        values.insert(key.to_string(), match config.config_type.as_str() {
-                            "string" => ValidValue::String(val.to_string()),
-                            "number" => ValidValue::Number(val.parse().unwrap()),
-                            _ => panic!("Unknown config type"),
-                        });
-                        i += 1;
+            let argv_index = i;
+            let depth = scopes.len() - 1;
+            let current = scopes[depth].brasp;
+            if arg == "--" {
+                rest = Some(i + 1);
+                break;
+            }
+            if (arg == "--help" || arg == "-h") && scopes.iter().any(|scope| scope.brasp.options.help) {
+                return Err(ParseError::HelpRequested {
+                    option: "help".to_string(),
+                    token: arg.clone(),
+                    index: Some(argv_index),
+                    usage: current.usage_in(&scopes[..depth].iter().map(|scope| scope.brasp).collect::<Vec<_>>()),
+                });
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (key, inline) = match long.split_once('=') {
+                    Some((key, val)) => (key, Some(val)),
+                    None => (long, None),
+                };
+                match find_long(&scopes, key) {
+                    Some((owner, key, config)) => {
+                        let value = read_value(key, config, inline, &args, &mut i)?;
+                        scopes[owner].result.record(key, config, value, argv_index);
                     }
+                    None => handle_unknown(&mut scopes, key, arg, argv_index)?,
                 }
-            } else if arg.starts_with("-") {
-                let short = &arg[1..];
-                if let Some(key) = self.short_options.get(short) {
-                    if let Some(config) = self.config_set.get(key) {
-                        if config.config_type == "boolean" {
-                            values.insert(key.to_string(), ValidValue::Boolean(true));
-                        } else if i + 1 < args.len() {
-                            let val = &args[i + 1];
-                            values.insert(key.to_string(), match config.config_type.as_str() {
-                                "string" => ValidValue::String(val.to_string()),
-                                "number" => ValidValue::Number(val.parse().unwrap()),
-                                _ => panic!("Unknown config type"),
-                            });
-                            i += 1;
+            } else if let Some(short) = arg.strip_prefix('-').filter(|short| !short.is_empty()) {
+                match expand_short(&scopes, short) {
+                    Ok(expanded) => {
+                        for (owner, key, config, inline) in expanded {
+                            let value = read_value(key, config, inline, &args, &mut i)?;
+                            scopes[owner].result.record(key, config, value, argv_index);
                         }
                     }
+                    Err(letter) => handle_unknown(&mut scopes, letter, arg, argv_index)?,
                 }
+            } else if let Some((name, command)) = current
+                .subcommands
+                .get_key_value(arg)
+                .filter(|_| scopes[depth].result.positionals.is_empty())
+            {
+                scopes.push(Scope {
+                    name,
+                    brasp: command,
+                    result: OptionsResult::default(),
+                });
+            } else if current.options.stop_at_positional {
+                rest = Some(i);
+                break;
             } else {
-                positionals.push(arg.clone());
+                current.check_positional(arg, i)?;
+                scopes[depth].result.positionals.push(arg.clone());
             }
             i += 1;
         }
 
-        OptionsResult {
-            values,
-            positionals,
+        let depth = scopes.len() - 1;
+        if let Some(start) = rest {
+            for (index, arg) in args.iter().enumerate().skip(start) {
+                scopes[depth].brasp.check_positional(arg, index)?;
+                scopes[depth].result.positionals.push(arg.clone());
+            }
         }
+
+        let mut result = OptionsResult::default();
+        let mut name = "";
+        while let Some(mut scope) = scopes.pop() {
+            if !name.is_empty() {
+                scope.result.subcommand = Some((name.to_string(), Box::new(result)));
+            }
+            name = scope.name;
+            result = scope.result;
+        }
+        Ok(result)
+    }
+
+    pub fn subcommand(&mut self, name: &str, command: Brasp) -> Result<(), DefinitionError> {
+        if !is_valid_name(name) || self.subcommands.contains_key(name) {
+            return Err(DefinitionError::from(ParseError::InvalidDefinition {
+                option: name.to_string(),
+                token: name.to_string(),
+                index: None,
+            }));
+        }
+        if self.options.help {
+            let errors = command.help_clashes();
+            if !errors.is_empty() {
+                return Err(DefinitionError { errors });
+            }
+        }
+        self.subcommands.insert(name.to_string(), command);
+        Ok(())
     }
 
-    pub fn validate(&self, o: &HashMap<String, ValidValue>) -> Result<(), String> {
-        for (field, value) in o {
-            let config = self.config_set.get(field).ok_or(format!("Unknown config option: {}", field))?;
-            validate_options(config, value)?;
+    pub fn group(&mut self, name: &str, group: OptionGroup) -> Result<(), DefinitionError> {
+        let invalid = |token: &str| ParseError::InvalidDefinition {
+            option: name.to_string(),
+            token: token.to_string(),
+            index: None,
+        };
+        let mut errors = Vec::new();
+        if !is_valid_name(name) || self.groups.contains_key(name) {
+            errors.push(invalid(name));
+        }
+        if group.options.is_empty() {
+            errors.push(invalid(""));
+        }
+        for option in group.options.iter().filter(|option| !self.config_set.contains_key(*option)) {
+            errors.push(invalid(option));
+        }
+        if !errors.is_empty() {
+            return Err(DefinitionError { errors });
         }
+        self.groups.insert(name.to_string(), group);
         Ok(())
     }
 
-    pub fn set_defaults_from_env(&mut self) {
-        if let Some(prefix) = &self.options.env_prefix {
-            for (key, option) in self.config_set.iter_mut() {
-                let env_key = to_env_key(prefix, key);
-                match env::var(&env_key) {
-                    Ok(val) => {
-                        let valid_val = from_env_val(&val, &option.config_type);
-                        option.default = Some(valid_val);
-                    },
-                    Err(_) => {}
+    pub(crate) fn check_references(&self) -> Vec<ParseError> {
+        let mut names: Vec<&String> = self.config_set.keys().collect();
+        names.sort();
+        let mut errors = Vec::new();
+        for name in names {
+            let option = &self.config_set[name];
+            let references = option.conflicts_with.iter().chain(&option.requires).chain(&option.required_unless);
+            for other in references.filter(|other| !self.config_set.contains_key(*other)) {
+                errors.push(ParseError::InvalidDefinition {
+                    option: name.clone(),
+                    token: other.clone(),
+                    index: None,
+                });
+            }
+        }
+        errors
+    }
+
+    fn check_positional(&self, arg: &str, index: usize) -> Result<(), ParseError> {
+        if self.options.allow_positionals {
+            return Ok(());
+        }
+        Err(ParseError::UnexpectedPositional {
+            option: String::new(),
+            token: arg.to_string(),
+            index: Some(index),
+        })
+    }
+
+    fn find_short(&self, short: &str) -> Option<(&str, &ConfigOptionBase)> {
+        let key = self.short_options.get(short)?;
+        let (key, config) = self.config_set.get_key_value(key)?;
+        Some((key, config))
+    }
+
+    pub fn parse(&self, args: Vec<String>) -> Result<OptionsResult, ParseError> {
+        let mut parsed = self.parse_raw(args)?;
+        self.apply_config_files(&mut parsed)?;
+        self.apply_defaults(&mut parsed);
+        Ok(parsed)
+    }
+
+    pub fn apply_defaults(&self, parsed: &mut OptionsResult) {
+        for (name, option) in &self.config_set {
+            if parsed.values.contains_key(name) {
+                continue;
+            }
+            if let Some(default) = &option.default {
+                parsed.values.insert(name.clone(), default.clone());
+                parsed.sources.insert(name.clone(), option.default_source.clone());
+            }
+        }
+        if let Some((name, sub)) = &mut parsed.subcommand {
+            if let Some(command) = self.subcommands.get(name) {
+                command.apply_defaults(sub);
+            }
+        }
+    }
+
+    /// Without provenance every value counts as given, which is right for the
+    /// output of `parse_raw`. Use `validate_parsed` after `parse`.
+    pub fn validate(&self, o: &HashMap<String, ValidValue>) -> Result<(), ValidationError> {
+        self.validate_values(o, &|name| o.get(name).is_some_and(is_set))
+    }
+
+    fn validate_values(&self, o: &HashMap<String, ValidValue>, given: &dyn Fn(&str) -> bool) -> Result<(), ValidationError> {
+        let mut errors = Vec::new();
+        let mut fields: Vec<(&String, &ValidValue)> = o.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (field, value) in fields {
+            match self.config_set.get(field) {
+                Some(config) => errors.extend(validate_options(field, config, value).err()),
+                None => errors.push(ParseError::UnknownOption {
+                    option: field.clone(),
+                    token: value.to_string(),
+                    index: None,
+                    suggestion: None,
+                }),
+            }
+        }
+        errors.extend(self.check_constraints(o, given));
+        if !errors.is_empty() {
+            return Err(ValidationError { errors });
+        }
+        Ok(())
+    }
+
+    /// Like `validate`, but also checks the values of the chosen subcommand,
+    /// and of its chosen subcommand in turn, against their own definitions.
+    /// Values filled in from plain defaults do not count as given for
+    /// `conflicts_with`, `requires`, `required_unless` and exclusive groups.
+    pub fn validate_parsed(&self, parsed: &OptionsResult) -> Result<(), ValidationError> {
+        let given = |name: &str| {
+            parsed.values.get(name).is_some_and(is_set) && parsed.sources.get(name) != Some(&ValueSource::Default)
+        };
+        let mut errors = self.validate_values(&parsed.values, &given).err().map(|e| e.errors).unwrap_or_default();
+        if let Some((name, sub)) = &parsed.subcommand {
+            if let Some(Err(e)) = self.subcommands.get(name).map(|command| command.validate_parsed(sub)) {
+                errors.extend(e.errors);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ValidationError { errors });
+        }
+        Ok(())
+    }
+
+    fn check_constraints(&self, o: &HashMap<String, ValidValue>, given: &dyn Fn(&str) -> bool) -> Vec<ParseError> {
+        let has_value = |name: &str| o.get(name).is_some_and(is_set);
+        let mut errors = Vec::new();
+
+        let mut names: Vec<&String> = self.config_set.keys().collect();
+        names.sort();
+        for name in names {
+            let option = &self.config_set[name];
+            if given(name) {
+                for other in option.conflicts_with.iter().filter(|other| given(other)) {
+                    errors.push(ParseError::ConflictingOptions {
+                        option: name.clone(),
+                        token: other.clone(),
+                        index: None,
+                    });
+                }
+                for other in option.requires.iter().filter(|other| !given(other)) {
+                    errors.push(ParseError::MissingOption {
+                        option: other.clone(),
+                        token: name.clone(),
+                        index: None,
+                    });
                 }
             }
+            if !has_value(name)
+                && (option.required || !option.required_unless.is_empty())
+                && !option.required_unless.iter().any(|other| given(other))
+            {
+                errors.push(ParseError::MissingOption {
+                    option: name.clone(),
+                    token: String::new(),
+                    index: None,
+                });
+            }
+        }
+
+        let mut groups: Vec<&String> = self.groups.keys().collect();
+        groups.sort();
+        for name in groups {
+            let group = &self.groups[name];
+            let chosen: Vec<&String> = group.options.iter().filter(|option| given(option)).collect();
+            if group.exclusive && chosen.len() > 1 {
+                errors.push(ParseError::ConflictingOptions {
+                    option: chosen[0].clone(),
+                    token: chosen[1..].iter().map(|option| option.as_str()).collect::<Vec<_>>().join(", "),
+                    index: None,
+                });
+            }
+            if group.required && !group.options.iter().any(|option| has_value(option)) {
+                errors.push(ParseError::MissingGroup {
+                    option: name.clone(),
+                    token: group.options.join(", "),
+                    index: None,
+                });
+            }
+        }
+        errors
+    }
+
+    /// Subcommands without an `env_prefix` of their own use their parent's.
+    pub fn set_defaults_from_env(&mut self) -> Result<(), ParseError> {
+        self.env_defaults(None)
+    }
+
+    fn env_defaults(&mut self, inherited: Option<&str>) -> Result<(), ParseError> {
+        let prefix = self.options.env_prefix.clone().or(inherited.map(str::to_string));
+        let prefix = prefix.as_deref();
+        let mut names: Vec<&String> = self.config_set.keys().collect();
+        names.sort();
+        let mut error = None;
+        let mut found = Vec::new();
+        for key in names {
+            let lookup = self.config_set[key]
+                .env_keys(prefix, key)
+                .into_iter()
+                .find_map(|env_key| env::var(&env_key).ok().map(|val| (env_key, val)));
+            let Some((env_key, val)) = lookup else {
+                continue;
+            };
+            match self.config_set[key].value_from_env(&val) {
+                Ok(value) => found.push((key.clone(), env_key, value)),
+                Err(reason) => {
+                    error.get_or_insert(ParseError::InvalidEnv {
+                        option: key.clone(),
+                        token: val,
+                        index: None,
+                        var: env_key,
+                        reason,
+                    });
+                }
+            }
+        }
+        for (key, env_key, value) in found {
+            if let Some(option) = self.config_set.get_mut(&key) {
+                option.default = Some(value);
+                option.default_source = ValueSource::Env { var: env_key };
+            }
+        }
+        let mut commands: Vec<&String> = self.subcommands.keys().collect();
+        commands.sort();
+        let commands: Vec<String> = commands.into_iter().cloned().collect();
+        for name in commands {
+            if let Some(Err(e)) = self.subcommands.get_mut(&name).map(|command| command.env_defaults(prefix)) {
+                error.get_or_insert(e);
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+type OptionMatch<'a> = (usize, &'a str, &'a ConfigOptionBase);
+type ShortMatch<'a> = (usize, &'a str, &'a ConfigOptionBase, Option<&'a str>);
+
+fn find_long<'a>(scopes: &[Scope<'a>], key: &str) -> Option<OptionMatch<'a>> {
+    scopes.iter().enumerate().rev().find_map(|(depth, scope)| {
+        let (key, config) = scope.brasp.config_set.get_key_value(key)?;
+        Some((depth, key.as_str(), config))
+    })
+}
+
+fn find_short<'a>(scopes: &[Scope<'a>], short: &str) -> Option<OptionMatch<'a>> {
+    scopes.iter().enumerate().rev().find_map(|(depth, scope)| {
+        let (key, config) = scope.brasp.find_short(short)?;
+        Some((depth, key, config))
+    })
+}
+
+fn expand_short<'a>(scopes: &[Scope<'a>], short: &'a str) -> Result<Vec<ShortMatch<'a>>, &'a str> {
+    if let Some((depth, key, config)) = find_short(scopes, short) {
+        return Ok(vec![(depth, key, config, None)]);
+    }
+    let mut expanded = Vec::new();
+    for (pos, c) in short.char_indices() {
+        let end = pos + c.len_utf8();
+        let letter = &short[pos..end];
+        let Some((depth, key, config)) = find_short(scopes, letter) else {
+            return Err(letter);
+        };
+        if config.config_type != ConfigType::Boolean {
+            let rest = &short[end..];
+            expanded.push((depth, key, config, Some(rest).filter(|rest| !rest.is_empty())));
+            break;
+        }
+        expanded.push((depth, key, config, None));
+    }
+    Ok(expanded)
+}
+
+fn handle_unknown(scopes: &mut [Scope], name: &str, arg: &str, index: usize) -> Result<(), ParseError> {
+    let depth = scopes.len() - 1;
+    match scopes[depth].brasp.options.unknown {
+        UnknownPolicy::Error => Err(unknown_option(scopes, name, arg, index)),
+        UnknownPolicy::Collect => {
+            scopes[depth].result.unknown.push(arg.to_string());
+            Ok(())
+        }
+        UnknownPolicy::Positional => {
+            scopes[depth].brasp.check_positional(arg, index)?;
+            scopes[depth].result.positionals.push(arg.to_string());
+            Ok(())
+        }
+    }
+}
+
+fn unknown_option(scopes: &[Scope], name: &str, arg: &str, index: usize) -> ParseError {
+    let suggestion = if name.chars().count() > 1 {
+        let mut candidates: Vec<&str> = scopes
+            .iter()
+            .flat_map(|scope| scope.brasp.config_set.keys().map(String::as_str))
+            .collect();
+        if scopes.iter().any(|scope| scope.brasp.options.help) {
+            candidates.push("help");
+        }
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= 2.max(name.len() / 3))
+            .min()
+            .map(|(_, candidate)| format!("--{}", candidate))
+    } else {
+        None
+    };
+    ParseError::UnknownOption {
+        option: name.to_string(),
+        token: arg.to_string(),
+        index: Some(index),
+        suggestion,
+    }
+}
+
+fn read_value(
+    name: &str,
+    config: &ConfigOptionBase,
+    inline: Option<&str>,
+    args: &[String],
+    i: &mut usize,
+) -> Result<ValidValue, ParseError> {
+    if config.config_type == ConfigType::Boolean {
+        if inline.is_some() {
+            return Err(ParseError::ValidationFailed {
+                option: name.to_string(),
+                token: args[*i].clone(),
+                index: Some(*i),
+            });
+        }
+        return Ok(ValidValue::Boolean(true));
+    }
+    let val = match inline {
+        Some(val) => val,
+        None => match args.get(*i + 1) {
+            Some(val) => {
+                *i += 1;
+                val
+            }
+            None => {
+                return Err(ParseError::MissingValue {
+                    option: name.to_string(),
+                    token: args[*i].clone(),
+                    index: Some(*i),
+                })
+            }
+        },
+    };
+    if let Some(parser) = &config.parser {
+        return parser.parse(val).map_err(|reason| ParseError::InvalidValue {
+            option: name.to_string(),
+            token: val.to_string(),
+            index: Some(*i),
+            reason,
+        });
+    }
+    match &config.config_type {
+        ConfigType::String | ConfigType::Custom(_) => Ok(config.canonicalize(ValidValue::String(val.to_string()))),
+        ConfigType::Number | ConfigType::Float | ConfigType::Unsigned => {
+            parse_number(val, &config.config_type).ok_or_else(|| ParseError::InvalidNumber {
+                option: name.to_string(),
+                token: val.to_string(),
+                index: Some(*i),
+            })
+        }
+        ConfigType::Boolean => Err(ParseError::ValidationFailed {
+            option: name.to_string(),
+            token: val.to_string(),
+            index: Some(*i),
+        }),
+    }
+}
+
+pub fn parse_number(token: &str, config_type: &ConfigType) -> Option<ValidValue> {
+    match config_type {
+        ConfigType::Number => {
+            let (negative, digits) = match token.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, token.strip_prefix('+').unwrap_or(token)),
+            };
+            let (radix, digits) = split_radix(digits)?;
+            let digits = if negative { format!("-{}", digits) } else { digits.to_string() };
+            i64::from_str_radix(&digits, radix).ok().map(ValidValue::Number)
         }
+        ConfigType::Unsigned => {
+            let (radix, digits) = split_radix(token.strip_prefix('+').unwrap_or(token))?;
+            u64::from_str_radix(digits, radix).ok().map(ValidValue::Unsigned)
+        }
+        ConfigType::Float => token.parse::<f64>().ok().filter(|n| n.is_finite()).map(ValidValue::Float),
+        ConfigType::String | ConfigType::Boolean | ConfigType::Custom(_) => None,
+    }
+}
+
+fn split_radix(token: &str) -> Option<(u32, &str)> {
+    let (radix, digits) = match token.get(..2) {
+        Some("0x" | "0X") => (16, &token[2..]),
+        Some("0o" | "0O") => (8, &token[2..]),
+        Some("0b" | "0B") => (2, &token[2..]),
+        _ => (10, token),
+    };
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    Some((radix, digits))
+}
+
+fn insert_value(values: &mut HashMap<String, ValidValue>, name: &str, config: &ConfigOptionBase, value: ValidValue) {
+    if !config.multiple {
+        values.insert(name.to_string(), value);
+        return;
     }
-}
\ No newline at end of file
+    let entry = values.entry(name.to_string());
+    if config.config_type == ConfigType::Boolean {
+        let count = entry.or_insert(ValidValue::Number(0));
+        if let ValidValue::Number(n) = count {
+            *n += 1;
+        }
+    } else if let ValidValue::List(vals) = entry.or_insert_with(|| ValidValue::List(Vec::new())) {
+        vals.push(value);
+    }
+}
+
+fn choose<'a>(choices: &'a [String], token: &str) -> Option<&'a String> {
+    let token = token.to_lowercase();
+    choices.iter().find(|choice| choice.to_lowercase() == token)
+}
+
+pub(crate) fn parse_bool(token: &str) -> Option<bool> {
+    match token.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_set(value: &ValidValue) -> bool {
+    match value {
+        ValidValue::Boolean(set) => *set,
+        ValidValue::List(vals) => !vals.is_empty(),
+        _ => true,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(char::is_alphanumeric) && chars.all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_number, Brasp, ConfigType, OptionGroup, ParseError, UnknownPolicy, ValidValue};
+
+    fn number(token: &str) -> Option<i64> {
+        match parse_number(token, &ConfigType::Number)? {
+            ValidValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn unsigned(token: &str) -> Option<u64> {
+        match parse_number(token, &ConfigType::Unsigned)? {
+            ValidValue::Unsigned(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn signs() {
+        assert_eq!(number("+5"), Some(5));
+        assert_eq!(number("-5"), Some(-5));
+        assert_eq!(number("+0x10"), Some(16));
+        assert_eq!(number("-0x10"), Some(-16));
+        assert_eq!(unsigned("+5"), Some(5));
+        for token in ["++5", "+-5", "-+5", "--5", "0x+5", "0x-5", "+", "-"] {
+            assert_eq!(number(token), None, "{}", token);
+        }
+        assert_eq!(unsigned("-5"), None);
+        assert_eq!(unsigned("+-5"), None);
+    }
+
+    #[test]
+    fn radix_literals() {
+        assert_eq!(number("0x1F"), Some(31));
+        assert_eq!(number("0o17"), Some(15));
+        assert_eq!(number("0b101"), Some(5));
+        assert_eq!(unsigned("0xFFFFFFFFFFFFFFFF"), Some(u64::MAX));
+        assert_eq!(number("0x"), None);
+        assert_eq!(number("0b2"), None);
+    }
+
+    #[test]
+    fn brasp_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Brasp>();
+    }
+
+    #[test]
+    fn unsigned_defaults() {
+        let brasp = Brasp::builder().unsigned("max").default(u64::MAX).unsigned("small").default(5).num("level").default(-3).build().unwrap();
+        let parsed = brasp.parse(Vec::new()).unwrap();
+        assert_eq!(parsed.get_u64("max").unwrap(), u64::MAX);
+        assert_eq!(parsed.get_u64("small").unwrap(), 5);
+        assert_eq!(parsed.get_i64("level").unwrap(), -3);
+        assert!(Brasp::builder().unsigned("size").default(-1).build().is_err());
+        assert!(Brasp::builder().num("level").default(1u64).build().is_err());
+    }
+
+    #[test]
+    fn unknown_as_positional_respects_allow_positionals() {
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let strict = Brasp::builder().unknown(UnknownPolicy::Positional).allow_positionals(false).build().unwrap();
+        assert!(matches!(strict.parse(args(&["--x"])), Err(ParseError::UnexpectedPositional { index: Some(0), .. })));
+        let lenient = Brasp::builder().unknown(UnknownPolicy::Positional).allow_positionals(true).build().unwrap();
+        assert_eq!(lenient.parse(args(&["--x"])).unwrap().positionals, vec!["--x"]);
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn check(brasp: &Brasp, list: &[&str]) -> Vec<ParseError> {
+        let parsed = brasp.parse(args(list)).unwrap();
+        brasp.validate_parsed(&parsed).err().map(|e| e.errors).unwrap_or_default()
+    }
+
+    #[test]
+    fn defaults_do_not_count_as_given() {
+        let brasp = Brasp::builder()
+            .flag("json")
+            .conflicts_with("format")
+            .opt("format")
+            .default("text")
+            .flag("upload")
+            .requires("target")
+            .opt("target")
+            .default("local")
+            .opt("url")
+            .required_unless("path")
+            .opt("path")
+            .default("/tmp")
+            .build()
+            .unwrap();
+        assert_eq!(check(&brasp, &["--json", "--url", "u"]), vec![]);
+        assert!(matches!(&check(&brasp, &["--json", "--format", "csv", "--url", "u"])[..], [ParseError::ConflictingOptions { .. }]));
+        assert!(matches!(&check(&brasp, &["--upload", "--url", "u"])[..], [ParseError::MissingOption { option, .. }] if option == "target"));
+        assert_eq!(check(&brasp, &["--upload", "--target", "s3", "--url", "u"]), vec![]);
+        assert!(matches!(&check(&brasp, &[])[..], [ParseError::MissingOption { option, .. }] if option == "url"));
+        assert_eq!(check(&brasp, &["--path", "/var"]), vec![]);
+    }
+
+    #[test]
+    fn groups_ignore_defaults() {
+        let brasp = Brasp::builder()
+            .opt("file")
+            .default("in.txt")
+            .opt("stdin-name")
+            .opt("color")
+            .default("auto")
+            .done()
+            .group("input", OptionGroup { options: vec!["file".to_string(), "stdin-name".to_string()], exclusive: true, required: false })
+            .group("style", OptionGroup { options: vec!["color".to_string()], exclusive: false, required: true })
+            .build()
+            .unwrap();
+        assert_eq!(check(&brasp, &["--stdin-name", "x"]), vec![]);
+        assert!(matches!(&check(&brasp, &["--stdin-name", "x", "--file", "f"])[..], [ParseError::ConflictingOptions { .. }]));
+        assert_eq!(check(&brasp, &[]), vec![]);
+    }
+
+    #[test]
+    fn env_values_count_as_given() {
+        std::env::set_var("BRASP_GIVEN_FORMAT", "csv");
+        let mut brasp = Brasp::builder().env_prefix("BRASP_GIVEN").flag("json").conflicts_with("format").opt("format").build().unwrap();
+        brasp.set_defaults_from_env().unwrap();
+        assert!(matches!(&check(&brasp, &["--json"])[..], [ParseError::ConflictingOptions { .. }]));
+    }
+
+    #[test]
+    fn subcommand_help_lists_global_options() {
+        let build = Brasp::builder().opt("target").num("jobs").describe("inner").build().unwrap();
+        let brasp = Brasp::builder()
+            .env_prefix("TOOL")
+            .help()
+            .flag("verbose")
+            .short('v')
+            .num("jobs")
+            .describe("outer")
+            .done()
+            .subcommand("build", build)
+            .build()
+            .unwrap();
+        let Err(ParseError::HelpRequested { usage, .. }) = brasp.parse(args(&["build", "--help"])) else {
+            panic!("expected help");
+        };
+        let (options, globals) = usage.split_once("Global options:").unwrap();
+        assert!(options.contains("--target") && options.contains("inner [env: TOOL_JOBS]"));
+        assert!(globals.contains("-v, --verbose") && globals.contains("[env: TOOL_VERBOSE]") && globals.contains("-h, --help"));
+        assert!(!globals.contains("outer"));
+    }
+
+    fn sample() -> Brasp {
+        Brasp::builder()
+            .unknown(UnknownPolicy::Error)
+            .flag("all")
+            .short('a')
+            .flag_list("verbose")
+            .short('v')
+            .opt("config")
+            .short('c')
+            .num("level")
+            .short('l')
+            .opt_list("include")
+            .short('I')
+            .build()
+            .unwrap()
+    }
+
+    // user-001: problems come back as errors carrying the argument index.
+    #[test]
+    fn parse_errors() {
+        let brasp = sample();
+        assert!(matches!(brasp.parse_raw(args(&["--config"])), Err(ParseError::MissingValue { index: Some(0), .. })));
+        assert!(matches!(brasp.parse_raw(args(&["-a", "--level", "x"])), Err(ParseError::InvalidNumber { index: Some(2), .. })));
+        assert!(matches!(brasp.parse_raw(args(&["--all=yes"])), Err(ParseError::ValidationFailed { .. })));
+    }
+
+    // user-002: repeated options accumulate, repeated flags count.
+    #[test]
+    fn accumulation() {
+        let parsed = sample().parse_raw(args(&["-I", "a", "--include", "b", "-v", "--verbose", "-v"])).unwrap();
+        assert_eq!(parsed.get_list::<String>("include").unwrap(), vec!["a", "b"]);
+        assert_eq!(parsed.get_i64("verbose").unwrap(), 3);
+        let parsed = sample().parse_raw(args(&["--level", "1", "--level", "2"])).unwrap();
+        assert_eq!(parsed.get_i64("level").unwrap(), 2);
+    }
+
+    // user-007: inline values with `=` and glued to short names.
+    #[test]
+    fn inline_values() {
+        let parsed = sample().parse_raw(args(&["--level=-3", "-cfoo", "--config=a=b", "-l", "-4"])).unwrap();
+        assert_eq!(parsed.get_i64("level").unwrap(), -4);
+        assert_eq!(parsed.get_str("config").unwrap(), "a=b");
+        let parsed = sample().parse_raw(args(&["-cfoo", "--level=-3"])).unwrap();
+        assert_eq!(parsed.get_str("config").unwrap(), "foo");
+        assert_eq!(parsed.get_i64("level").unwrap(), -3);
+        let parsed = sample().parse_raw(args(&["--config="])).unwrap();
+        assert_eq!(parsed.get_str("config").unwrap(), "");
+    }
+
+    // user-008: clustered short flags, with a value-taking option last.
+    #[test]
+    fn clustered_shorts() {
+        let parsed = sample().parse_raw(args(&["-avvv"])).unwrap();
+        assert!(parsed.get_bool("all").unwrap());
+        assert_eq!(parsed.get_i64("verbose").unwrap(), 3);
+        let parsed = sample().parse_raw(args(&["-avc", "file"])).unwrap();
+        assert_eq!(parsed.get_str("config").unwrap(), "file");
+        let parsed = sample().parse_raw(args(&["-vcfile"])).unwrap();
+        assert_eq!(parsed.get_str("config").unwrap(), "file");
+        assert!(matches!(sample().parse_raw(args(&["-avx"])), Err(ParseError::UnknownOption { option, .. }) if option == "x"));
+    }
+
+    // user-009: `--`, strict positionals and stopping at the first positional.
+    #[test]
+    fn positionals() {
+        let lenient = Brasp::builder().allow_positionals(true).flag("all").short('a').build().unwrap();
+        let parsed = lenient.parse_raw(args(&["x", "-a", "--", "-x", "--all"])).unwrap();
+        assert_eq!(parsed.positionals, vec!["x", "-x", "--all"]);
+        assert!(parsed.get_bool("all").unwrap());
+
+        let strict = Brasp::builder().allow_positionals(false).flag("all").build().unwrap();
+        assert!(matches!(strict.parse_raw(args(&["--all", "x"])), Err(ParseError::UnexpectedPositional { index: Some(1), .. })));
+        assert!(matches!(strict.parse_raw(args(&["--", "x"])), Err(ParseError::UnexpectedPositional { index: Some(1), .. })));
+
+        let stopping = Brasp::builder().allow_positionals(true).stop_at_positional(true).flag("all").build().unwrap();
+        let parsed = stopping.parse_raw(args(&["--all", "run", "--all", "-x"])).unwrap();
+        assert_eq!(parsed.positionals, vec!["run", "--all", "-x"]);
+    }
+
+    // user-010: unknown options are rejected with a suggestion, or collected.
+    #[test]
+    fn unknown_options() {
+        match sample().parse_raw(args(&["--verbos"])) {
+            Err(ParseError::UnknownOption { suggestion, index, .. }) => {
+                assert_eq!(suggestion.as_deref(), Some("--verbose"));
+                assert_eq!(index, Some(0));
+            }
+            other => panic!("expected UnknownOption, got {:?}", other),
+        }
+        assert!(matches!(sample().parse_raw(args(&["--zzzzzz"])), Err(ParseError::UnknownOption { suggestion: None, .. })));
+        let collect = Brasp::builder().unknown(UnknownPolicy::Collect).flag("all").build().unwrap();
+        let parsed = collect.parse_raw(args(&["--nope", "--all", "-q"])).unwrap();
+        assert_eq!(parsed.unknown, vec!["--nope", "-q"]);
+        assert!(parsed.get_bool("all").unwrap());
+    }
+
+    // user-011: options are recorded on the scope that defines them.
+    #[test]
+    fn subcommand_scoping() {
+        let build = Brasp::builder().opt("target").flag("verbose").build().unwrap();
+        let tool = Brasp::builder().flag("quiet").num("jobs").done().subcommand("build", build).build().unwrap();
+
+        let parsed = tool.parse_raw(args(&["--jobs", "2", "build", "--quiet", "--target", "x"])).unwrap();
+        assert!(parsed.get_bool("quiet").unwrap());
+        assert_eq!(parsed.get_i64("jobs").unwrap(), 2);
+        let (name, build) = parsed.subcommand.as_ref().unwrap();
+        assert_eq!(name, "build");
+        assert_eq!(build.get_str("target").unwrap(), "x");
+        assert!(!build.values.contains_key("quiet"));
+
+        let tool = Brasp::builder().flag("verbose").done().subcommand("build", Brasp::builder().opt("target").build().unwrap()).build().unwrap();
+        let parsed = tool.parse_raw(args(&["build", "--verbose"])).unwrap();
+        assert!(parsed.get_bool("verbose").unwrap());
+        assert!(parsed.subcommand.unwrap().1.values.is_empty());
+    }
+
+    // user-014: typed accessors and their errors.
+    #[test]
+    fn accessors() {
+        let brasp = Brasp::builder().opt("name").float("ratio").opt_list("tag").num("level").build().unwrap();
+        let parsed = brasp.parse(args(&["--name", "n", "--ratio", "0.5", "--tag", "a"])).unwrap();
+        assert_eq!(parsed.get_str("name").unwrap(), "n");
+        assert_eq!(parsed.get_f64("ratio").unwrap(), 0.5);
+        assert_eq!(parsed.get_list::<String>("tag").unwrap(), vec!["a"]);
+        assert_eq!(parsed.get_list::<String>("name").unwrap(), vec!["n"]);
+        assert_eq!(parsed.get_or("level", 7i64).unwrap(), 7);
+        assert!(matches!(parsed.get_i64("name"), Err(ParseError::TypeMismatch { .. })));
+        assert!(matches!(parsed.get_i64("level"), Err(ParseError::MissingOption { .. })));
+    }
+
+    // user-020: choices match case-insensitively and keep their spelling.
+    #[test]
+    fn choices() {
+        let brasp = Brasp::builder().opt("color").choices(&["auto", "Never"]).build().unwrap();
+        let parsed = brasp.parse(args(&["--color", "NEVER"])).unwrap();
+        assert_eq!(parsed.get_str("color").unwrap(), "Never");
+        let parsed = brasp.parse(args(&["--color", "blue"])).unwrap();
+        match brasp.validate_parsed(&parsed).unwrap_err().errors.as_slice() {
+            [ParseError::InvalidValue { reason, .. }] => assert_eq!(reason, "expected one of auto, Never"),
+            other => panic!("expected InvalidValue, got {:?}", other),
+        }
+        assert!(Brasp::builder().num("level").choices(&["a"]).build().is_err());
+    }
+
+    // user-023: boolean spellings from the environment.
+    #[test]
+    fn env_booleans() {
+        for (text, expected) in [("true", true), ("YES", true), ("On", true), ("1", true), ("false", false), ("no", false), ("OFF", false), ("0", false)] {
+            assert!(matches!(super::from_env_val(text, &ConfigType::Boolean), Ok(ValidValue::Boolean(b)) if b == expected), "{}", text);
+        }
+        for text in ["", "maybe", "2", "y"] {
+            assert!(matches!(super::from_env_val(text, &ConfigType::Boolean), Err(ParseError::InvalidValue { .. })), "{}", text);
+        }
+    }
+
+    // user-024: per-option env names, alias precedence and opting out.
+    #[test]
+    fn env_aliases() {
+        std::env::set_var("BRASP_ALIAS_LOW", "low");
+        std::env::set_var("BRASP_ALIAS_TOKEN", "secret");
+        let build = || {
+            Brasp::builder()
+                .env_prefix("BRASP_ALIAS")
+                .opt("proxy")
+                .env("BRASP_ALIAS_HIGH")
+                .env("BRASP_ALIAS_LOW")
+                .opt("token")
+                .no_env()
+                .build()
+                .unwrap()
+        };
+        let mut brasp = build();
+        brasp.set_defaults_from_env().unwrap();
+        let parsed = brasp.parse(Vec::new()).unwrap();
+        assert_eq!(parsed.get_str("proxy").unwrap(), "low");
+        assert_eq!(parsed.sources["proxy"], super::ValueSource::Env { var: "BRASP_ALIAS_LOW".to_string() });
+        assert!(!parsed.values.contains_key("token"));
+
+        std::env::set_var("BRASP_ALIAS_HIGH", "high");
+        let mut brasp = build();
+        brasp.set_defaults_from_env().unwrap();
+        assert_eq!(brasp.parse(Vec::new()).unwrap().get_str("proxy").unwrap(), "high");
+        assert!(Brasp::builder().opt("x").env("X").no_env().build().is_err());
+    }
+
+    // user-025: list values split on a separator, with `\` escapes.
+    #[test]
+    fn env_lists() {
+        use super::{join_env_list, split_env_list};
+        assert_eq!(split_env_list(r"a:b\:c:d\\", ':'), vec!["a", "b:c", r"d\"]);
+        assert_eq!(split_env_list("", ','), Vec::<String>::new());
+        assert_eq!(split_env_list("a,,b", ','), vec!["a", "", "b"]);
+        let values: Vec<ValidValue> = ["x,y", r"back\slash", ""].into_iter().map(ValidValue::from).collect();
+        let joined = join_env_list(&values, ',');
+        assert_eq!(joined, r"x\,y,back\\slash,");
+        assert_eq!(split_env_list(&joined, ','), vec!["x,y", r"back\slash", ""]);
+
+        std::env::set_var("BRASP_LISTS_PATH", "/a:/b");
+        std::env::set_var("BRASP_LISTS_PORT", "1,x");
+        let mut brasp = Brasp::builder().env_prefix("BRASP_LISTS").opt_list("path").env_separator(':').num_list("port").build().unwrap();
+        match brasp.set_defaults_from_env() {
+            Err(ParseError::InvalidEnv { var, .. }) => assert_eq!(var, "BRASP_LISTS_PORT"),
+            other => panic!("expected InvalidEnv, got {:?}", other),
+        }
+        assert_eq!(brasp.parse(Vec::new()).unwrap().get_list::<String>("path").unwrap(), vec!["/a", "/b"]);
+    }
+}
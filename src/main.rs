@@ -1,57 +1,46 @@
 use std::env;
-use std::collections::HashMap;
-use brasp::{Brasp, BraspOptions, ValidValue, ConfigOptionBase, Validator};
+use std::error::Error;
+use brasp::Brasp;
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    
-    let mut brasp = Brasp {
-        config_set: HashMap::new(),
-        short_options: HashMap::new(),
-        options: BraspOptions {
-            allow_positionals: true,
-            env_prefix: Some("MYAPP".to_string()),
-            usage: None,
-        },
-    };
 
-    brasp.opt(HashMap::from([(
-        "config".to_string(),
-        ConfigOptionBase {
-            config_type: "string".to_string(),
-            short: Some("c".to_string()),
-            default: None,
-            description: Some("Configuration file path".to_string()),
-            validate: Some(Validator::None),
-            multiple: false,
-        },
-    )]));
+    let mut brasp = Brasp::builder()
+        .env_prefix("MYAPP")
+        .usage("Usage: brasp [options]")
+        .help()
+        .opt("config")
+        .short('c')
+        .config_file()
+        .describe("Configuration file path")
+        .flag("verbose")
+        .short('v')
+        .default(false)
+        .describe("Enable verbose output")
+        .build()?;
+    brasp.set_defaults_from_env()?;
 
-    brasp.flag(HashMap::from([(
-        "verbose".to_string(),
-        ConfigOptionBase {
-            config_type: "boolean".to_string(),
-            short: Some("v".to_string()),
-            default: Some(ValidValue::Boolean(false)),
-            description: Some("Enable verbose output".to_string()),
-            validate: Some(Validator::None),
-            multiple: false,
-        },
-    )]));
+    let parsed_values = match brasp.parse(args[1..].to_vec()) {
+        Ok(parsed) => parsed,
+        Err(e) if e.is_help() => {
+            print!("{}", e);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
-    let parsed_values = brasp.parse_raw(args[1..].to_vec());
-    
-    if let Some(config) = parsed_values.values.get("config") {
+    if let Ok(config) = parsed_values.get_str("config") {
         println!("Config value: {}", config);
     }
 
-    if let Some(verbose) = parsed_values.values.get("verbose") {
-        println!("Verbose mode: {}", verbose);
+    if parsed_values.get_or("verbose", false)? {
+        println!("Verbose mode: true");
     } else {
         println!("Verbose mode is off");
     }
 
-    if let Some(usage) = brasp.options.usage.clone() {
-        println!("{}", usage);
-    }
-}
\ No newline at end of file
+    Ok(())
+}
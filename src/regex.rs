@@ -0,0 +1,554 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+pub struct Pattern {
+    source: String,
+    compiled: OnceLock<Result<Vec<Inst>, String>>,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Self {
+        Pattern {
+            source: source.to_string(),
+            compiled: OnceLock::new(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn compile(&self) -> Result<(), String> {
+        match self.program() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Runs all threads of the program side by side over the input (a Pike
+    /// VM), so matching takes time linear in the length of `text` and never
+    /// recurses on the input.
+    pub fn is_match(&self, text: &str) -> bool {
+        let Ok(program) = self.program() else {
+            return false;
+        };
+        let chars: Vec<char> = text.chars().collect();
+        let mut current = Vec::new();
+        let mut next = Vec::new();
+        let mut marks = vec![usize::MAX; program.len()];
+        for pos in 0..=chars.len() {
+            // Starting a thread at every position makes the pattern unanchored.
+            add_thread(program, &mut current, &mut marks, 0, pos, chars.len());
+            for &pc in &current {
+                let advance = match &program[pc] {
+                    Inst::Match => return true,
+                    Inst::Char(c) => chars.get(pos) == Some(c),
+                    Inst::Any => chars.get(pos).is_some_and(|c| *c != '\n'),
+                    Inst::Class(class) => chars.get(pos).is_some_and(|c| class.matches(*c)),
+                    _ => false,
+                };
+                if advance {
+                    add_thread(program, &mut next, &mut marks, pc + 1, pos + 1, chars.len());
+                }
+            }
+            current.clear();
+            std::mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    fn program(&self) -> &Result<Vec<Inst>, String> {
+        self.compiled.get_or_init(|| {
+            let node = Parser::new(&self.source).parse()?;
+            let mut compiler = Compiler { program: Vec::new() };
+            compiler.compile(&node)?;
+            compiler.emit(Inst::Match)?;
+            Ok(compiler.program)
+        })
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(source: &str) -> Self {
+        Pattern::new(source)
+    }
+}
+
+impl From<String> for Pattern {
+    fn from(source: String) -> Self {
+        Pattern::new(&source)
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Pattern").field(&self.source).finish()
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != *negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != *negated,
+            ClassItem::Space(negated) => c.is_whitespace() != *negated,
+        }
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.items.iter().any(|item| item.matches(c)) != self.negated
+    }
+}
+
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err("pattern is too large".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => self.emit(Inst::Char(*c)).map(drop),
+            Node::Any => self.emit(Inst::Any).map(drop),
+            Node::Class(class) => self.emit(Inst::Class(class.clone())).map(drop),
+            Node::Start => self.emit(Inst::Start).map(drop),
+            Node::End => self.emit(Inst::End).map(drop),
+            Node::Concat(nodes) => nodes.iter().try_for_each(|node| self.compile(node)),
+            Node::Alt(alts) => {
+                let mut jumps = Vec::new();
+                for (i, alt) in alts.iter().enumerate() {
+                    if i + 1 == alts.len() {
+                        self.compile(alt)?;
+                        break;
+                    }
+                    let split = self.emit(Inst::Split(0, 0))?;
+                    self.compile(alt)?;
+                    jumps.push(self.emit(Inst::Jmp(0))?);
+                    self.program[split] = Inst::Split(split + 1, self.program.len());
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jmp(end);
+                }
+                Ok(())
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.emit(Inst::Jmp(split))?;
+                        self.program[split] = self.branch(split + 1, self.program.len(), *greedy);
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let split = self.emit(Inst::Split(0, 0))?;
+                            self.compile(node)?;
+                            self.program[split] = self.branch(split + 1, self.program.len(), *greedy);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn branch(&self, body: usize, skip: usize, greedy: bool) -> Inst {
+        match greedy {
+            true => Inst::Split(body, skip),
+            false => Inst::Split(skip, body),
+        }
+    }
+}
+
+/// Follows jumps, splits and anchors from `pc` and queues every instruction
+/// that consumes a character. `marks` keeps a thread from being queued twice
+/// for the same position, which also stops empty loops.
+fn add_thread(program: &[Inst], list: &mut Vec<usize>, marks: &mut [usize], pc: usize, pos: usize, len: usize) {
+    let mut stack = vec![pc];
+    while let Some(pc) = stack.pop() {
+        if marks[pc] == pos {
+            continue;
+        }
+        marks[pc] = pos;
+        match &program[pc] {
+            Inst::Jmp(to) => stack.push(*to),
+            Inst::Split(first, second) => {
+                stack.push(*second);
+                stack.push(*first);
+            }
+            Inst::Start if pos == 0 => stack.push(pc + 1),
+            Inst::End if pos == len => stack.push(pc + 1),
+            Inst::Start | Inst::End => {}
+            _ => list.push(pc),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<Node, String> {
+        let node = self.parse_alt()?;
+        match self.peek() {
+            None => Ok(node),
+            Some(c) => Err(format!("unexpected {:?} at position {}", c, self.pos)),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.parse_concat()?];
+        while self.eat('|') {
+            alts.push(self.parse_concat()?);
+        }
+        if alts.len() == 1 {
+            return Ok(alts.remove(0));
+        }
+        Ok(Node::Alt(alts))
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_repeat(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let at = self.pos;
+        match self.next() {
+            Some('(') => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err(format!("unsupported group syntax at position {}", at));
+                }
+                let node = self.parse_alt()?;
+                if !self.eat(')') {
+                    return Err(format!("unclosed group opened at position {}", at));
+                }
+                Ok(node)
+            }
+            Some('[') => self.parse_class(at),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => self.parse_escape(at),
+            Some(c @ ('*' | '+' | '?' | '{')) => Err(format!("nothing to repeat before {:?} at position {}", c, at)),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn parse_escape(&mut self, at: usize) -> Result<Node, String> {
+        match self.next() {
+            Some('n') => Ok(Node::Char('\n')),
+            Some('t') => Ok(Node::Char('\t')),
+            Some('r') => Ok(Node::Char('\r')),
+            Some(c) => match class_escape(c) {
+                Some(item) => Ok(Node::Class(Class {
+                    negated: false,
+                    items: vec![item],
+                })),
+                None if c.is_alphanumeric() => Err(format!("unknown escape \\{} at position {}", c, at)),
+                None => Ok(Node::Char(c)),
+            },
+            None => Err("trailing backslash at end of pattern".to_string()),
+        }
+    }
+
+    fn parse_class(&mut self, at: usize) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => break,
+                Some(c) => c,
+                None => return Err(format!("unclosed character class opened at position {}", at)),
+            };
+            first = false;
+            let lo = if c == '\\' {
+                let Some(escaped) = self.next() else {
+                    return Err("trailing backslash at end of pattern".to_string());
+                };
+                if let Some(item) = class_escape(escaped) {
+                    items.push(item);
+                    continue;
+                }
+                unescape(escaped)
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let hi = match self.next() {
+                    Some('\\') => self.next().map(unescape).ok_or("trailing backslash at end of pattern")?,
+                    Some(c) => c,
+                    None => return Err(format!("unclosed character class opened at position {}", at)),
+                };
+                if hi < lo {
+                    return Err(format!("invalid range {}-{} in character class", lo, hi));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+        Ok(Node::Class(Class { negated, items }))
+    }
+
+    fn parse_repeat(&mut self, mut atom: Node) -> Result<Node, String> {
+        loop {
+            let at = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*' | '+' | '?' | '{') => match self.next() {
+                    Some('*') => (0, None),
+                    Some('+') => (1, None),
+                    Some('?') => (0, Some(1)),
+                    _ => self.parse_counts(at)?,
+                },
+                _ => return Ok(atom),
+            };
+            if matches!(atom, Node::Start | Node::End) {
+                return Err(format!("nothing to repeat at position {}", at));
+            }
+            let greedy = !self.eat('?');
+            atom = Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    fn parse_counts(&mut self, at: usize) -> Result<(usize, Option<usize>), String> {
+        let min = self.parse_number().ok_or_else(|| format!("invalid repetition at position {}", at))?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.parse_number().ok_or_else(|| format!("invalid repetition at position {}", at))?)
+            }
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') {
+            return Err(format!("unclosed repetition at position {}", at));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid repetition range at position {}", at));
+        }
+        Ok((min, max))
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+}
+
+fn class_escape(c: char) -> Option<ClassItem> {
+    match c {
+        'd' => Some(ClassItem::Digit(false)),
+        'D' => Some(ClassItem::Digit(true)),
+        'w' => Some(ClassItem::Word(false)),
+        'W' => Some(ClassItem::Word(true)),
+        's' => Some(ClassItem::Space(false)),
+        'S' => Some(ClassItem::Space(true)),
+        _ => None,
+    }
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+    use std::time::{Duration, Instant};
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        let pattern = Pattern::new(pattern);
+        pattern.compile().unwrap();
+        pattern.is_match(text)
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^abc$", "abc"));
+        assert!(!matches("^abc$", "xabc"));
+        assert!(!matches("^abc$", "abcx"));
+        assert!(matches("abc", "xxabcxx"));
+        assert!(matches("^", ""));
+        assert!(matches("^$", ""));
+        assert!(!matches("^$", "a"));
+        assert!(!matches("a^b", "ab"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("^[a-z]+$", "hello"));
+        assert!(!matches("^[a-z]+$", "Hello"));
+        assert!(matches("^[^0-9]+$", "abc"));
+        assert!(!matches("^[^0-9]+$", "a1c"));
+        assert!(matches(r"^\d\w\s$", "1_ "));
+        assert!(!matches(r"^\D$", "5"));
+        assert!(matches("^[-a]+$", "-a-"));
+        assert!(matches(r"^[\]x]+$", "]x"));
+        assert!(matches("^.$", "x"));
+        assert!(!matches("^.$", "\n"));
+    }
+
+    #[test]
+    fn repeat_counts() {
+        assert!(matches("^a{3}$", "aaa"));
+        assert!(!matches("^a{3}$", "aa"));
+        assert!(!matches("^a{3}$", "aaaa"));
+        assert!(matches("^a{2,4}$", "aa"));
+        assert!(matches("^a{2,4}$", "aaaa"));
+        assert!(!matches("^a{2,4}$", "aaaaa"));
+        assert!(matches("^a{2,}$", "aaaaaaa"));
+        assert!(!matches("^a{2,}$", "a"));
+        assert!(matches("^ab?c$", "ac"));
+        assert!(matches("^ab*?c$", "abbc"));
+        assert!(matches("^(a*)*$", "aaa"));
+        assert!(matches("^(a|)+b$", "aab"));
+    }
+
+    #[test]
+    fn alternation() {
+        assert!(matches("^(cat|dog|bird)$", "dog"));
+        assert!(matches("^(cat|dog|bird)$", "bird"));
+        assert!(!matches("^(cat|dog|bird)$", "cow"));
+        assert!(matches("^(?:ab|a)c$", "ac"));
+        assert!(matches("x|^y", "ay x"));
+        assert!(!matches("^x|^y", "ay"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for source in ["(a", "[a-", "a{3", "*a", r"\q", "a{3,1}", "[z-a]", "(?=a)", "a)"] {
+            assert!(Pattern::new(source).compile().is_err(), "{}", source);
+            assert!(!Pattern::new(source).is_match("a"));
+        }
+        assert!(Pattern::new("(a{1000}){1000}").compile().is_err());
+    }
+
+    #[test]
+    fn long_inputs() {
+        let long = "a".repeat(100_000);
+        assert!(matches("^[a-z]+$", &long));
+        assert!(!matches("^[a-z]+$", &format!("{}!", long)));
+        assert!(matches("b", &format!("{}b", long)));
+        assert!(!matches("ab", &long));
+
+        let start = Instant::now();
+        let text = format!("{}!", "a".repeat(5_000));
+        assert!(!matches("^(a+)+$", &text));
+        assert!(!matches("^(a|aa)*(a*)*b$", &text));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,186 @@
+use std::env;
+
+use crate::{Brasp, ConfigOptionBase, ConfigType};
+
+const DEFAULT_WIDTH: usize = 80;
+const MAX_NAME_COLUMN: usize = 30;
+
+impl Brasp {
+    pub fn usage(&self) -> String {
+        self.usage_with_width(terminal_width())
+    }
+
+    pub fn usage_with_width(&self, width: usize) -> String {
+        self.render(width, &[])
+    }
+
+    /// Renders usage for a subcommand reached through `parents`, outermost
+    /// first. Their options are accepted here too, so they are listed as
+    /// global options, and env names may come from a parent prefix.
+    pub(crate) fn usage_in(&self, parents: &[&Brasp]) -> String {
+        self.render(terminal_width(), parents)
+    }
+
+    fn render(&self, width: usize, parents: &[&Brasp]) -> String {
+        let scope = |depth: usize| parents.get(depth).copied().unwrap_or(self);
+        let prefix_at = |depth: usize| (0..=depth).rev().find_map(|depth| scope(depth).options.env_prefix.as_deref());
+        let prefix = prefix_at(parents.len());
+        let mut names: Vec<&String> = self.config_set.keys().collect();
+        names.sort();
+
+        let mut rows: Vec<(String, Vec<String>)> = names
+            .into_iter()
+            .map(|name| (option_names(name, &self.config_set[name]), option_help(name, &self.config_set[name], prefix)))
+            .collect();
+        if self.options.help {
+            rows.push(("-h, --help".to_string(), words("Print this help message")));
+        }
+
+        // Inner scopes shadow outer ones, the same way option lookup does.
+        let mut seen: Vec<&String> = self.config_set.keys().collect();
+        let mut globals: Vec<(&String, String, Vec<String>)> = Vec::new();
+        for (depth, parent) in parents.iter().enumerate().rev() {
+            let prefix = prefix_at(depth);
+            for (name, option) in &parent.config_set {
+                if seen.contains(&name) {
+                    continue;
+                }
+                seen.push(name);
+                globals.push((name, option_names(name, option), option_help(name, option, prefix)));
+            }
+        }
+        globals.sort_by(|a, b| a.0.cmp(b.0));
+        let mut globals: Vec<(String, Vec<String>)> = globals.into_iter().map(|(_, names, help)| (names, help)).collect();
+        if !self.options.help && parents.iter().any(|parent| parent.options.help) {
+            globals.push(("-h, --help".to_string(), words("Print this help message")));
+        }
+
+        let mut commands: Vec<&String> = self.subcommands.keys().collect();
+        commands.sort();
+        let commands: Vec<(String, Vec<String>)> = commands
+            .into_iter()
+            .map(|name| {
+                let description = self.subcommands[name].options.description.as_deref().unwrap_or("");
+                (name.clone(), words(description))
+            })
+            .collect();
+
+        let mut out = String::new();
+        if let Some(usage) = &self.options.usage {
+            out.push_str(usage);
+            out.push_str("\n\n");
+        }
+        if !commands.is_empty() {
+            render_section(&mut out, "Commands", commands, width);
+        }
+        if !rows.is_empty() {
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            render_section(&mut out, "Options", rows, width);
+        }
+        if !globals.is_empty() {
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            render_section(&mut out, "Global options", globals, width);
+        }
+        out
+    }
+}
+
+fn option_help(name: &str, option: &ConfigOptionBase, prefix: Option<&str>) -> Vec<String> {
+    let mut parts = Vec::new();
+    if let Some(description) = &option.description {
+        parts.extend(words(description));
+    }
+    if option.required {
+        parts.push("[required]".to_string());
+    }
+    if let Some(default) = &option.default {
+        parts.push(format!("[default: {}]", default));
+    }
+    let env_keys = option.env_keys(prefix, name);
+    if !env_keys.is_empty() {
+        parts.push(format!("[env: {}]", env_keys.join(", ")));
+    }
+    parts
+}
+
+fn render_section(out: &mut String, title: &str, rows: Vec<(String, Vec<String>)>, width: usize) {
+    out.push_str(title);
+    out.push_str(":\n");
+    let name_column = rows
+        .iter()
+        .map(|(names, _)| names.len())
+        .filter(|len| *len <= MAX_NAME_COLUMN)
+        .max()
+        .unwrap_or(0);
+    let indent = 2 + name_column + 2;
+    let text_width = width.saturating_sub(indent).max(20);
+
+    for (names, help) in rows {
+        out.push_str("  ");
+        out.push_str(&names);
+        let lines = wrap(&help, text_width);
+        let mut lines = lines.iter();
+        if names.len() <= name_column {
+            if let Some(first) = lines.next() {
+                out.push_str(&" ".repeat(name_column - names.len() + 2));
+                out.push_str(first);
+            }
+        }
+        out.push('\n');
+        for line in lines {
+            out.push_str(&" ".repeat(indent));
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+fn option_names(name: &str, option: &ConfigOptionBase) -> String {
+    let mut out = match &option.short {
+        Some(short) => format!("-{}, --{}", short, name),
+        None => format!("    --{}", name),
+    };
+    if let Some(choices) = option.choices() {
+        out.push_str(&format!(" <{}>", choices.join("|")));
+    } else if option.config_type != ConfigType::Boolean {
+        out.push_str(&format!(" <{}>", option.config_type));
+    }
+    if option.multiple {
+        out.push_str("...");
+    }
+    out
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|cols| cols.trim().parse().ok())
+        .filter(|cols| *cols > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+fn wrap(words: &[String], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
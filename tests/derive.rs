@@ -0,0 +1,99 @@
+use brasp::{Brasp, Choice, FromArgs, ParseError};
+
+#[derive(Choice, Debug, PartialEq)]
+enum LogLevel {
+    Debug,
+    Info,
+    #[brasp(name = "warning")]
+    Warn,
+    VeryLoud,
+}
+
+#[derive(Brasp, Debug)]
+#[brasp(env_prefix = "BRASP_DERIVE_TEST")]
+struct Args {
+    #[brasp(short = 'c')]
+    config: Option<String>,
+    ratio: Option<f64>,
+    #[brasp(short = 'I')]
+    include_dir: Vec<String>,
+    port: Vec<u64>,
+    #[brasp(short = 'v', count)]
+    verbose: i64,
+    quiet: bool,
+    #[brasp(choice, default = "info")]
+    log_level: LogLevel,
+    #[brasp(default = 8080u64, range(1, 65535))]
+    listen: u64,
+    #[brasp(default = 0.5, range(0.0, 1.0))]
+    threshold: f64,
+    #[brasp(name = "jobs", default = 1)]
+    workers: i64,
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    std::iter::once("tool").chain(list.iter().copied()).map(str::to_string).collect()
+}
+
+fn errors(list: &[&str]) -> Vec<ParseError> {
+    Args::from_arg_list(args(list)).unwrap_err().errors
+}
+
+#[test]
+fn defaults() {
+    let parsed = Args::from_arg_list(args(&[])).unwrap();
+    assert_eq!(parsed.config, None);
+    assert_eq!(parsed.ratio, None);
+    assert!(parsed.include_dir.is_empty());
+    assert!(parsed.port.is_empty());
+    assert_eq!(parsed.verbose, 0);
+    assert!(!parsed.quiet);
+    assert_eq!(parsed.log_level, LogLevel::Info);
+    assert_eq!(parsed.listen, 8080);
+    assert_eq!(parsed.threshold, 0.5);
+    assert_eq!(parsed.workers, 1);
+}
+
+#[test]
+fn field_shapes() {
+    let parsed = Args::from_arg_list(args(&[
+        "-c", "app.toml", "--ratio", "0.25", "-I", "a", "-I", "b", "--port", "80", "--port", "443", "-vvv", "--quiet", "--listen",
+        "9000", "--threshold", "1", "--jobs", "4",
+    ]))
+    .unwrap();
+    assert_eq!(parsed.config.as_deref(), Some("app.toml"));
+    assert_eq!(parsed.ratio, Some(0.25));
+    assert_eq!(parsed.include_dir, vec!["a", "b"]);
+    assert_eq!(parsed.port, vec![80, 443]);
+    assert_eq!(parsed.verbose, 3);
+    assert!(parsed.quiet);
+    assert_eq!(parsed.listen, 9000);
+    assert_eq!(parsed.threshold, 1.0);
+    assert_eq!(parsed.workers, 4);
+}
+
+#[test]
+fn choices() {
+    assert_eq!(LogLevel::CHOICES, &["debug", "info", "warning", "very-loud"]);
+    let level = |value: &str| Args::from_arg_list(args(&["--log-level", value])).map(|parsed| parsed.log_level);
+    assert_eq!(level("DEBUG").unwrap(), LogLevel::Debug);
+    assert_eq!(level("warning").unwrap(), LogLevel::Warn);
+    assert_eq!(level("very-loud").unwrap(), LogLevel::VeryLoud);
+    assert!(matches!(errors(&["--log-level", "warn"]).as_slice(), [ParseError::InvalidValue { .. }]));
+}
+
+#[test]
+fn ranges() {
+    assert!(matches!(errors(&["--listen", "0"]).as_slice(), [ParseError::ValidationFailed { .. }]));
+    assert!(matches!(errors(&["--listen", "-1"]).as_slice(), [ParseError::InvalidNumber { .. }]));
+    assert!(matches!(errors(&["--threshold", "1.5"]).as_slice(), [ParseError::ValidationFailed { .. }]));
+    assert!(Args::from_arg_list(args(&["--listen", "65535", "--threshold", "0"])).is_ok());
+}
+
+#[test]
+fn definitions() {
+    let brasp = Args::brasp().unwrap();
+    assert!(brasp.config_set["verbose"].multiple);
+    assert_eq!(brasp.config_set["log-level"].choices().unwrap(), LogLevel::CHOICES);
+    assert!(!brasp.config_set.contains_key("workers"));
+}